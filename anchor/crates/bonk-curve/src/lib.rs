@@ -0,0 +1,371 @@
+// =================================================================
+// BONK-CURVE - xy=k BONDING CURVE MATH
+// =================================================================
+// Pure, no_std, Anchor-free. The on-chain program is the only caller
+// today; the keeper and frontend under app/ still run their own
+// TypeScript math and should quote through quote_buy / quote_sell
+// until they link this crate. Every division names its rounding
+// direction.
+// =================================================================
+
+#![no_std]
+
+use core::fmt;
+
+// =================================================================
+// ERRORS & ROUNDING
+// =================================================================
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CurveError {
+    MathOverflow,
+    DivisionByZero,
+    ExceedsSupply,
+    EmptyReserve,
+}
+
+impl fmt::Display for CurveError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CurveError::MathOverflow => f.write_str("math overflow"),
+            CurveError::DivisionByZero => f.write_str("division by zero"),
+            CurveError::ExceedsSupply => f.write_str("amount exceeds curve supply"),
+            CurveError::EmptyReserve => f.write_str("virtual SOL reserve is empty"),
+        }
+    }
+}
+
+pub type Result<T> = core::result::Result<T, CurveError>;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Rounding {
+    Down,
+    Up,
+}
+
+pub fn div(numerator: u128, denominator: u128, rounding: Rounding) -> Result<u128> {
+    if denominator == 0 {
+        return Err(CurveError::DivisionByZero);
+    }
+
+    let quotient = numerator / denominator;
+    let remainder = numerator % denominator;
+    match rounding {
+        Rounding::Up if remainder > 0 => quotient.checked_add(1).ok_or(CurveError::MathOverflow),
+        _ => Ok(quotient),
+    }
+}
+
+fn to_u64(value: u128) -> Result<u64> {
+    u64::try_from(value).map_err(|_| CurveError::MathOverflow)
+}
+
+// =================================================================
+// FEES
+// =================================================================
+
+// Fee charged on amount, rounded down
+pub fn fee_amount(amount: u64, fee_bps: u64) -> Result<u64> {
+    let fee = div(
+        (amount as u128)
+            .checked_mul(fee_bps as u128)
+            .ok_or(CurveError::MathOverflow)?,
+        10_000,
+        Rounding::Down,
+    )?;
+
+    to_u64(fee)
+}
+
+// Smallest gross amount whose net (gross - fee_amount) reaches net
+pub fn gross_for_net(net: u64, fee_bps: u64) -> Result<u64> {
+    if fee_bps >= 10_000 {
+        return Err(CurveError::DivisionByZero);
+    }
+
+    let mut gross = to_u64(div(
+        (net as u128)
+            .checked_mul(10_000)
+            .ok_or(CurveError::MathOverflow)?,
+        10_000 - fee_bps as u128,
+        Rounding::Up,
+    )?)?;
+
+    // fee_amount rounds down, so a lamport or two less may still do
+    while gross > 0 && gross - 1 - fee_amount(gross - 1, fee_bps)? >= net {
+        gross -= 1;
+    }
+
+    Ok(gross)
+}
+
+// =================================================================
+// TRADES
+// =================================================================
+// Constant product on the pool's own virtual reserves (x = SOL,
+// y = tokens). Every amount paid out of the pool rounds down and
+// every amount paid into it rounds up, so x * y never shrinks and a
+// buy-then-sell round trip never returns more SOL than went in.
+// =================================================================
+
+// Tokens out for net_sol_in entering the pool: y * n / (x + n), rounded
+// down and clamped to what is left above virtual_token_final. Returns 0
+// once the curve is exhausted. With x = 0 any deposit would take the
+// whole remaining curve, so an empty SOL reserve is an error.
+pub fn buy_amount(
+    net_sol_in: u64,
+    virtual_sol_reserve: u64,
+    virtual_token_reserve: u64,
+    virtual_token_final: u64,
+) -> Result<u64> {
+    if virtual_sol_reserve == 0 {
+        return Err(CurveError::EmptyReserve);
+    }
+    if virtual_token_reserve <= virtual_token_final {
+        return Ok(0);
+    }
+
+    let new_virtual_sol = (virtual_sol_reserve as u128)
+        .checked_add(net_sol_in as u128)
+        .ok_or(CurveError::MathOverflow)?;
+
+    let tokens_out = div(
+        (virtual_token_reserve as u128)
+            .checked_mul(net_sol_in as u128)
+            .ok_or(CurveError::MathOverflow)?,
+        new_virtual_sol,
+        Rounding::Down,
+    )?;
+
+    let max_tokens = (virtual_token_reserve - virtual_token_final) as u128;
+
+    to_u64(tokens_out.min(max_tokens))
+}
+
+// Inverse of buy_amount: smallest net_sol_in whose forward quote yields
+// at least token_amount, x * t / (y - t) rounded up. Same empty-reserve
+// rule as buy_amount.
+pub fn buy_cost_for_tokens(
+    token_amount: u64,
+    virtual_sol_reserve: u64,
+    virtual_token_reserve: u64,
+    virtual_token_final: u64,
+) -> Result<u64> {
+    if virtual_sol_reserve == 0 {
+        return Err(CurveError::EmptyReserve);
+    }
+    let max_tokens = virtual_token_reserve.saturating_sub(virtual_token_final);
+    if token_amount > max_tokens {
+        return Err(CurveError::ExceedsSupply);
+    }
+
+    let sol_in = div(
+        (virtual_sol_reserve as u128)
+            .checked_mul(token_amount as u128)
+            .ok_or(CurveError::MathOverflow)?,
+        (virtual_token_reserve - token_amount) as u128,
+        Rounding::Up,
+    )?;
+
+    to_u64(sol_in)
+}
+
+// Gross SOL out for token_amount entering the pool: x * t / (y + t),
+// rounded down.
+pub fn sell_amount(
+    token_amount: u64,
+    virtual_sol_reserve: u64,
+    virtual_token_reserve: u64,
+) -> Result<u64> {
+    let new_virtual_token = (virtual_token_reserve as u128)
+        .checked_add(token_amount as u128)
+        .ok_or(CurveError::MathOverflow)?;
+
+    let sol_out = div(
+        (virtual_sol_reserve as u128)
+            .checked_mul(token_amount as u128)
+            .ok_or(CurveError::MathOverflow)?,
+        new_virtual_token,
+        Rounding::Down,
+    )?;
+
+    to_u64(sol_out)
+}
+
+// Largest virtual SOL reserve at which selling every outstanding token
+// (virtual_token_init - virtual_token_reserve) pays out no more than
+// sol_collected. Used to re-derive x when real liquidity is removed.
+// May be 0 when almost nothing is left, which buys then refuse.
+pub fn max_solvent_virtual_sol(
+    sol_collected: u64,
+    virtual_token_reserve: u64,
+    virtual_token_init: u64,
+) -> Result<u64> {
+    let outstanding = virtual_token_init
+        .checked_sub(virtual_token_reserve)
+        .ok_or(CurveError::MathOverflow)?;
+
+    if outstanding == 0 {
+        return Ok(u64::MAX);
+    }
+
+    let max_virtual_sol = div(
+        (sol_collected as u128)
+            .checked_mul(virtual_token_init as u128)
+            .ok_or(CurveError::MathOverflow)?,
+        outstanding as u128,
+        Rounding::Down,
+    )?;
+
+    Ok(u64::try_from(max_virtual_sol).unwrap_or(u64::MAX))
+}
+
+// =================================================================
+// VALUATION
+// =================================================================
+
+// Market cap in USD (6 decimals price) of total_supply at the spot
+// price x / y. Both divisions round down.
+pub fn market_cap_usd(
+    virtual_sol_reserve: u64,
+    virtual_token_reserve: u64,
+    sol_price_usd: u64,
+    total_supply: u64,
+) -> Result<u64> {
+    let mc_lamports = div(
+        (virtual_sol_reserve as u128)
+            .checked_mul(total_supply as u128)
+            .ok_or(CurveError::MathOverflow)?,
+        virtual_token_reserve as u128,
+        Rounding::Down,
+    )?;
+
+    lamports_to_usd(to_u64(mc_lamports)?, sol_price_usd)
+}
+
+// lamports * price / 1e9 / 1e6, rounded down
+pub fn lamports_to_usd(lamports: u64, sol_price_usd: u64) -> Result<u64> {
+    let scaled = (lamports as u128)
+        .checked_mul(sol_price_usd as u128)
+        .ok_or(CurveError::MathOverflow)?;
+
+    to_u64(div(scaled, 1_000_000_000 * 1_000_000, Rounding::Down)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // xorshift64*, so the properties run over the same inputs every time
+    struct Rng(u64);
+
+    impl Rng {
+        fn next(&mut self) -> u64 {
+            self.0 ^= self.0 >> 12;
+            self.0 ^= self.0 << 25;
+            self.0 ^= self.0 >> 27;
+            self.0.wrapping_mul(0x2545_f491_4f6c_dd1d)
+        }
+
+        fn range(&mut self, lo: u64, hi: u64) -> u64 {
+            lo + self.next() % (hi - lo + 1)
+        }
+    }
+
+    const ITERATIONS: usize = 10_000;
+
+    // Virtual reserves in the range the tiers actually use
+    fn reserves(rng: &mut Rng) -> (u64, u64, u64) {
+        let virtual_sol = rng.range(1_000_000, 1_000_000_000_000);
+        let virtual_token = rng.range(1_000_000_000, 1_000_000_000_000_000_000);
+        let virtual_final = rng.range(0, virtual_token / 2);
+        (virtual_sol, virtual_token, virtual_final)
+    }
+
+    #[test]
+    fn buy_then_sell_never_returns_more_sol() {
+        let mut rng = Rng(0x5eed_0001);
+        for _ in 0..ITERATIONS {
+            let (x, y, y_final) = reserves(&mut rng);
+            let sol_in = rng.range(1, 100_000_000_000);
+
+            let tokens = buy_amount(sol_in, x, y, y_final).unwrap();
+            let sol_out = sell_amount(tokens, x + sol_in, y - tokens).unwrap();
+
+            assert!(sol_out <= sol_in, "x={x} y={y} in={sol_in} out={sol_out}");
+        }
+    }
+
+    #[test]
+    fn buy_cost_for_tokens_inverts_buy_amount() {
+        let mut rng = Rng(0x5eed_0002);
+        for _ in 0..ITERATIONS {
+            let (x, y, y_final) = reserves(&mut rng);
+            let tokens = rng.range(1, y - y_final);
+
+            let cost = buy_cost_for_tokens(tokens, x, y, y_final).unwrap();
+
+            assert!(buy_amount(cost, x, y, y_final).unwrap() >= tokens);
+            assert!(cost == 0 || buy_amount(cost - 1, x, y, y_final).unwrap() < tokens);
+        }
+
+        assert_eq!(buy_cost_for_tokens(11, 10, 100, 90), Err(CurveError::ExceedsSupply));
+    }
+
+    #[test]
+    fn gross_for_net_is_minimal() {
+        let mut rng = Rng(0x5eed_0003);
+        for _ in 0..ITERATIONS {
+            let net = rng.range(0, 1_000_000_000_000);
+            let fee_bps = rng.range(0, 9_999);
+
+            let gross = gross_for_net(net, fee_bps).unwrap();
+
+            assert!(gross - fee_amount(gross, fee_bps).unwrap() >= net);
+            assert!(gross == 0 || gross - 1 - fee_amount(gross - 1, fee_bps).unwrap() < net);
+        }
+
+        assert_eq!(gross_for_net(1, 10_000), Err(CurveError::DivisionByZero));
+    }
+
+    #[test]
+    fn max_solvent_virtual_sol_is_solvent_and_maximal() {
+        let mut rng = Rng(0x5eed_0004);
+        for _ in 0..ITERATIONS {
+            let y_init = rng.range(1_000_000_000, 1_000_000_000_000_000_000);
+            let y = rng.range(1, y_init - 1);
+            let sol_collected = rng.range(0, 1_000_000_000_000);
+            let outstanding = y_init - y;
+
+            let x = max_solvent_virtual_sol(sol_collected, y, y_init).unwrap();
+            if x == u64::MAX {
+                continue;
+            }
+
+            // Selling every outstanding token at x is covered...
+            assert!(sell_amount(outstanding, x, y).unwrap() <= sol_collected);
+            // ...and one lamport more of virtual SOL would not be
+            assert!((x as u128 + 1) * outstanding as u128 > sol_collected as u128 * y_init as u128);
+        }
+
+        assert_eq!(max_solvent_virtual_sol(5, 100, 100), Ok(u64::MAX));
+    }
+
+    #[test]
+    fn drained_reserve_refuses_buys() {
+        let mut rng = Rng(0x5eed_0005);
+        for _ in 0..ITERATIONS {
+            let y_init = rng.range(1_000_000_000, 1_000_000_000_000_000_000);
+            let y = rng.range(1, y_init - 1);
+            let y_final = rng.range(0, y / 2);
+
+            // Tokens still out but no SOL left to back them: x clamps to 0
+            let x = max_solvent_virtual_sol(0, y, y_init).unwrap();
+            assert_eq!(x, 0);
+
+            let sol_in = rng.range(1, 100_000_000_000);
+            assert_eq!(buy_amount(sol_in, x, y, y_final), Err(CurveError::EmptyReserve));
+            assert_eq!(buy_cost_for_tokens(1, x, y, y_final), Err(CurveError::EmptyReserve));
+        }
+    }
+}
@@ -10,35 +10,59 @@
 
 use anchor_lang::prelude::*;
 use anchor_lang::system_program;
+use anchor_lang::solana_program::program_option::COption;
 use anchor_lang::solana_program::rent::Rent;
 use anchor_spl::{
     associated_token::AssociatedToken,
     token_interface::{Mint, TokenAccount, TokenInterface, MintTo},
 };
+use anchor_spl::token_2022::spl_token_2022::{
+    extension::ExtensionType,
+    instruction::AuthorityType,
+    state::Mint as MintState,
+};
+use anchor_spl::token_interface::spl_pod::optional_keys::OptionalNonZeroPubkey;
+use anchor_spl::token_interface::spl_token_metadata_interface::state::TokenMetadata;
+use bonk_curve::CurveError;
 
 declare_id!("F2iP4tpfg5fLnxNQ2pA2odf7V9kq4uS9pV3MpARJT5eD");
 
 // =================================================================
-// SECURITY CONSTANTS - HARDCODED FOR BULLETPROOF OPERATION
+// GLOBAL CONFIG - admin, keeper and treasury live on-chain
 // =================================================================
 
-const TREASURY_WALLET: &str = "5t46DVegMLyVQ2nstgPPUNDn5WCEFwgQCXfbSx1nHrdf";
-const KEEPER_AUTHORITY: &str = "65UHQMfEmBjuAhN1Hg4bWC1jkdHC9eWMsaB1MC58Jgea";
+const GLOBAL_CONFIG_SEED: &[u8] = b"global_config";
+
+// Bump whenever GlobalConfig gains fields; new fields are appended at the
+// end and migrate_config fills their defaults
+const CONFIG_VERSION: u8 = 1;
+
+// Bump whenever TokenBattleState gains fields; migrate_battle_state fills the defaults
+const BATTLE_STATE_VERSION: u8 = 8;
+const ROLE_REGISTRY_SEED: &[u8] = b"role_registry";
+const MAX_ROLE_MEMBERS: usize = 32;
+const LAUNCH_ALLOCATION_SEED: &[u8] = b"launch_allocation";
+const CREATOR_VAULT_SEED: &[u8] = b"creator_vault";
+const REFERRAL_VAULT_SEED: &[u8] = b"referral_vault";
+const FEE_VAULT_SEED: &[u8] = b"fee_vault";
+const FEE_SHARD_SEED: &[u8] = b"fee_shard";
+const FEE_SHARD_COUNT: u8 = 16;
+const TRADER_ACTIVITY_SEED: &[u8] = b"trader_activity";
+const USER_POSITION_SEED: &[u8] = b"user_position";
 
 // =================================================================
 // TOKEN SUPPLY PARAMETERS - 1 BILLION MULTIPLIER (xy=k)
 // =================================================================
 
 const TOTAL_SUPPLY: u64 = 1_000_000_000_000_000_000; // 1B * 10^9 = 10^18
-const BONDING_CURVE_SUPPLY: u64 = 999_968_377_000_000_000; // 99.997% (999,968,377 * 10^9)
-const RAYDIUM_RESERVED_SUPPLY: u64 = 31_623_000_000_000; // 0.003% (31,623 * 10^9)
 
 // =================================================================
 // BONDING CURVE xy=k PARAMETERS - 1 BILLION MULTIPLIER
 // =================================================================
 
-// Active tier selector (change this to switch tiers)
-const USE_TEST_TIER: bool = true;
+// Tier IDs passed to create_battle_token
+const TIER_TEST: u8 = 0;
+const TIER_PROD: u8 = 1;
 
 // =================================================================
 // ============ TIER TEST (Devnet) - 1B MULTIPLIER ============
@@ -65,89 +89,524 @@ const PROD_VICTORY_VOLUME_SOL: u64 = 8_800_835_000_000_000;
 const PROD_QUALIFICATION_SOL: u64 = 1;
 
 // =================================================================
-// TIER SELECTOR FUNCTIONS
+// TIER SELECTOR
 // =================================================================
 
-const fn get_virtual_sol_init() -> u64 {
-    if USE_TEST_TIER { TEST_VIRTUAL_SOL_INIT } else { PROD_VIRTUAL_SOL_INIT }
+struct TierParams {
+    virtual_sol_init: u64,
+    virtual_token_init: u64,
+    virtual_token_final: u64,
+    // Informational: x * y at launch, for indexers. The curve always
+    // trades on the live reserves and never reads this back.
+    constant_k: u128,
+    target_sol: u64,
+    victory_volume_sol: u64,
+    qualification_sol: u64,
+}
+
+fn get_tier_params(tier: u8) -> Result<TierParams> {
+    match tier {
+        TIER_TEST => Ok(TierParams {
+            virtual_sol_init: TEST_VIRTUAL_SOL_INIT,
+            virtual_token_init: TEST_VIRTUAL_TOKEN_INIT,
+            virtual_token_final: TEST_VIRTUAL_TOKEN_FINAL,
+            constant_k: TEST_CONSTANT_K,
+            target_sol: TEST_TARGET_SOL,
+            victory_volume_sol: TEST_VICTORY_VOLUME_SOL,
+            qualification_sol: TEST_QUALIFICATION_SOL,
+        }),
+        TIER_PROD => Ok(TierParams {
+            virtual_sol_init: PROD_VIRTUAL_SOL_INIT,
+            virtual_token_init: PROD_VIRTUAL_TOKEN_INIT,
+            virtual_token_final: PROD_VIRTUAL_TOKEN_FINAL,
+            constant_k: PROD_CONSTANT_K,
+            target_sol: PROD_TARGET_SOL,
+            victory_volume_sol: PROD_VICTORY_VOLUME_SOL,
+            qualification_sol: PROD_QUALIFICATION_SOL,
+        }),
+        _ => Err(BonkError::InvalidTier.into()),
+    }
 }
 
-const fn get_virtual_token_init() -> u64 {
-    if USE_TEST_TIER { TEST_VIRTUAL_TOKEN_INIT } else { PROD_VIRTUAL_TOKEN_INIT }
+// =================================================================
+// TIMELOCKED PARAMETERS - defaults written by initialize_config
+// =================================================================
+// Live values sit in GlobalConfig and only change through
+// queue_param_change + execute_param_change after PARAM_CHANGE_DELAY
+// =================================================================
+
+const DEFAULT_TRADING_FEE_BPS: u64 = 200; // 2.00% - Created phase (base fee)
+const DEFAULT_QUALIFIED_FEE_BPS: u64 = 200; // 2.00%
+const DEFAULT_IN_BATTLE_FEE_BPS: u64 = 200; // 2.00%
+const DEFAULT_NEAR_TARGET_FEE_BPS: u64 = 200; // 2.00% - InBattle, close to target_sol
+const DEFAULT_NEAR_TARGET_THRESHOLD_BPS: u64 = 9_000; // near target from 90% of target_sol
+const DEFAULT_SWEEP_LIMIT_PER_EPOCH: u64 = 0; // 0 = no per-epoch limit on sweep_fees
+const DEFAULT_WASH_TRADE_WINDOW_SECS: u64 = 300; // 5 minutes
+const DEFAULT_BATTLE_VOLUME_TARGET_BPS: u64 = 10_000; // 100% of the tier's victory volume
+const DEFAULT_PLATFORM_FEE_BPS: u64 = 500; // 5.00%
+const DEFAULT_MATCHMAKING_TOLERANCE_SOL: u64 = 10_000_000; // 0.01 SOL
+const DEFAULT_MAX_SOL_PER_TX: u64 = 100_000_000_000_000; // 100,000 SOL max
+const DEFAULT_VICTORY_TOLERANCE_BPS: u64 = 9999; // 99.99% for precise Raydium allocation
+const DEFAULT_LAUNCH_WINDOW_SECS: u64 = 60; // anti-snipe window after creation
+const DEFAULT_LAUNCH_WALLET_CAP_SOL: u64 = 1_000_000_000; // 1 SOL per wallet during the window
+const DEFAULT_CREATOR_FEE_SHARE_BPS: u64 = 2_000; // 20% of each trading fee
+const DEFAULT_REFERRAL_FEE_SHARE_BPS: u64 = 1_000; // 10% of each referred trading fee
+
+const PARAM_CHANGE_DELAY: i64 = 172_800; // 48 hours
+const PARAM_CHANGE_SEED: &[u8] = b"param_change";
+
+// Hard bounds - a queued value outside these is rejected
+const MAX_TRADING_FEE_BPS: u64 = 500; // 5.00%
+const MAX_PLATFORM_FEE_BPS: u64 = 1_000; // 10.00%
+const MAX_MATCHMAKING_TOLERANCE_SOL: u64 = 100_000_000_000; // 100 SOL
+const MIN_VICTORY_TOLERANCE_BPS: u64 = 9_000; // 90.00%
+const MAX_VICTORY_TOLERANCE_BPS: u64 = 10_000; // 100.00%
+const MAX_NEAR_TARGET_THRESHOLD_BPS: u64 = 10_000; // 100.00%
+const MAX_SWEEP_LIMIT_PER_EPOCH: u64 = u64::MAX;
+const MAX_WASH_TRADE_WINDOW_SECS: u64 = 86_400; // 24 hours
+const MIN_BATTLE_VOLUME_TARGET_BPS: u64 = 1_000; // 10% - a zero target would let a token win on start_battle
+const MAX_BATTLE_VOLUME_TARGET_BPS: u64 = 100_000; // 10x
+const MAX_LAUNCH_WINDOW_SECS: u64 = 3_600; // 1 hour
+const MAX_CREATOR_FEE_SHARE_BPS: u64 = 5_000; // 50% of each trading fee
+const MAX_REFERRAL_FEE_SHARE_BPS: u64 = 5_000; // 50% of each trading fee
+
+// =================================================================
+// SECURITY LIMITS
+// =================================================================
+
+const MIN_SOL_PER_TX: u64 = 1; // 1 lamport minimum
+
+// =================================================================
+// EMERGENCY PAUSE - bounded so it can never freeze funds forever
+// =================================================================
+
+const MAX_PAUSE_DURATION: i64 = 259_200; // 72 hours
+const PAUSE_COOLDOWN: i64 = 86_400; // 24 hours of trading between pauses
+
+// =================================================================
+// ORACLE UPDATE INTERVAL
+// =================================================================
+
+const PRICE_UPDATE_INTERVAL: i64 = 86400; // 24 hours
+
+// =================================================================
+// 🛡️ HELPER: Check if victory conditions are met
+// =================================================================
+
+// Judged on what the token gained since start_battle, against the
+// targets snapshotted then, so pre-battle trading cannot pre-win it
+fn check_victory_conditions_met(
+    state: &TokenBattleState,
+    sol_collected: u64,
+    battle_volume: u64,
+) -> bool {
+    let (sol_gained, volume_gained) = battle_gains(state, sol_collected, battle_volume);
+
+    sol_gained >= state.battle_target_sol && volume_gained >= state.battle_target_volume
 }
 
-const fn get_virtual_token_final() -> u64 {
-    if USE_TEST_TIER { TEST_VIRTUAL_TOKEN_FINAL } else { PROD_VIRTUAL_TOKEN_FINAL }
+// SOL and battle volume gained since the start_battle snapshot
+fn battle_gains(state: &TokenBattleState, sol_collected: u64, battle_volume: u64) -> (u64, u64) {
+    (
+        sol_collected.saturating_sub(state.battle_start_sol),
+        battle_volume.saturating_sub(state.battle_start_volume),
+    )
 }
 
-const fn get_constant_k() -> u128 {
-    if USE_TEST_TIER { TEST_CONSTANT_K } else { PROD_CONSTANT_K }
+// Built the same way by every path that locks a winner, once
+// victory_timestamp is set
+fn victory_achieved_event(state: &TokenBattleState, sol_price: u64) -> Result<VictoryAchieved> {
+    let (sol_gained, volume_gained) = battle_gains(state, state.sol_collected, state.battle_volume);
+
+    Ok(VictoryAchieved {
+        winner_mint: state.mint,
+        sol_collected: state.sol_collected,
+        volume_sol: state.total_trade_volume,
+        battle_volume_sol: state.battle_volume,
+        sol_gained,
+        volume_gained,
+        battle_target_sol: state.battle_target_sol,
+        battle_target_volume: state.battle_target_volume,
+        final_mc_usd: bonk_curve::market_cap_usd(
+            state.virtual_sol_reserve,
+            state.virtual_token_reserve,
+            sol_price,
+            TOTAL_SUPPLY,
+        ).map_err(BonkError::from)?,
+        final_volume_usd: bonk_curve::lamports_to_usd(state.total_trade_volume, sol_price)
+            .map_err(BonkError::from)?,
+        victory_timestamp: state.victory_timestamp,
+    })
 }
 
-const fn get_target_sol() -> u64 {
-    if USE_TEST_TIER { TEST_TARGET_SOL } else { PROD_TARGET_SOL }
+// SOL a token must gain in battle to reach the graduation threshold,
+// and the battle volume it must trade on top of what it had. The volume
+// target is never 0, so even a token already at the SOL threshold has
+// to trade in battle before it can win.
+fn battle_targets(state: &TokenBattleState, config: &GlobalConfig) -> Result<(u64, u64)> {
+    let sol_threshold = state.target_sol
+        .checked_mul(config.victory_tolerance_bps)
+        .ok_or(BonkError::MathOverflow)?
+        / 10000;
+    let target_volume = (state.victory_volume_sol as u128)
+        .checked_mul(config.battle_volume_target_bps as u128)
+        .ok_or(BonkError::MathOverflow)?
+        / 10000;
+
+    Ok((
+        sol_threshold.saturating_sub(state.sol_collected),
+        u64::try_from(target_volume).map_err(|_| BonkError::MathOverflow)?.max(1),
+    ))
 }
 
-const fn get_victory_volume_sol() -> u64 {
-    if USE_TEST_TIER { TEST_VICTORY_VOLUME_SOL } else { PROD_VICTORY_VOLUME_SOL }
+// =================================================================
+// 🛡️ HELPER: Wash-trade filter for battle volume
+// =================================================================
+// A trade that reverses the same wallet's opposite-side trade on the
+// same mint within wash_trade_window_secs still counts toward
+// total_trade_volume, but not toward the battle_volume used for victory
+
+#[derive(Clone, Copy)]
+enum TradeSide {
+    Buy,
+    Sell,
 }
 
-const fn get_qualification_sol() -> u64 {
-    if USE_TEST_TIER { TEST_QUALIFICATION_SOL } else { PROD_QUALIFICATION_SOL }
+fn is_wash_trade(activity: &TraderActivity, side: TradeSide, now: i64, window_secs: u64) -> bool {
+    let opposite_timestamp = match side {
+        TradeSide::Buy => activity.last_sell_timestamp,
+        TradeSide::Sell => activity.last_buy_timestamp,
+    };
+
+    opposite_timestamp != 0 && (now.saturating_sub(opposite_timestamp) as u64) < window_secs
 }
 
-const TARGET_SOL: u64 = get_target_sol();
-const VICTORY_VOLUME_SOL: u64 = get_victory_volume_sol();
-const QUALIFICATION_SOL: u64 = get_qualification_sol();
+fn record_trader_activity(
+    activity: &mut TraderActivity,
+    mint: Pubkey,
+    trader: Pubkey,
+    bump: u8,
+    side: TradeSide,
+    now: i64,
+) {
+    activity.mint = mint;
+    activity.trader = trader;
+    activity.bump = bump;
+    match side {
+        TradeSide::Buy => activity.last_buy_timestamp = now,
+        TradeSide::Sell => activity.last_sell_timestamp = now,
+    }
+}
 
 // =================================================================
-// MATCHMAKING & BATTLE PARAMETERS
+// HELPER: Per-user position (cost basis and volume)
 // =================================================================
 
-const MATCHMAKING_TOLERANCE_SOL: u64 = if USE_TEST_TIER {
-    10_000_000  // 0.01 SOL for test
-} else {
-    1_000_000_000 // 1 SOL for prod
-};
+fn record_user_position(
+    position: &mut UserPosition,
+    mint: Pubkey,
+    user: Pubkey,
+    bump: u8,
+    side: TradeSide,
+    token_amount: u64,
+    sol_amount: u64,
+) -> Result<()> {
+    let now = Clock::get()?.unix_timestamp;
+
+    position.mint = mint;
+    position.user = user;
+    position.bump = bump;
+    match side {
+        TradeSide::Buy => {
+            position.tokens_bought = position.tokens_bought
+                .checked_add(token_amount)
+                .ok_or(BonkError::MathOverflow)?;
+            position.sol_in = position.sol_in
+                .checked_add(sol_amount)
+                .ok_or(BonkError::MathOverflow)?;
+        }
+        TradeSide::Sell => {
+            position.tokens_sold = position.tokens_sold
+                .checked_add(token_amount)
+                .ok_or(BonkError::MathOverflow)?;
+            position.sol_out = position.sol_out
+                .checked_add(sol_amount)
+                .ok_or(BonkError::MathOverflow)?;
+        }
+    }
+    position.trade_count = position.trade_count
+        .checked_add(1)
+        .ok_or(BonkError::MathOverflow)?;
+    if position.first_trade_timestamp == 0 {
+        position.first_trade_timestamp = now;
+    }
+    position.last_trade_timestamp = now;
+
+    Ok(())
+}
 
 // =================================================================
-// FEE STRUCTURE
+// 🛡️ HELPER: Emergency pause window
 // =================================================================
 
-const TRADING_FEE_BPS: u64 = 200; // 2.00%
-const PLATFORM_FEE_BPS: u64 = 500; // 5.00%
+fn is_paused(paused_until: i64, now: i64) -> bool {
+    now < paused_until
+}
+
+fn next_pause_deadline(paused_until: i64, now: i64, duration: i64) -> Result<i64> {
+    require!(
+        duration > 0 && duration <= MAX_PAUSE_DURATION,
+        BonkError::InvalidPauseDuration
+    );
+    require!(!is_paused(paused_until, now), BonkError::AlreadyPaused);
+
+    if paused_until != 0 {
+        let cooldown_end = paused_until
+            .checked_add(PAUSE_COOLDOWN)
+            .ok_or(BonkError::MathOverflow)?;
+        require!(now >= cooldown_end, BonkError::PauseCooldownActive);
+    }
+
+    Ok(now.checked_add(duration).ok_or(BonkError::MathOverflow)?)
+}
 
 // =================================================================
-// SECURITY LIMITS
+// 🛡️ HELPER: Trade expiry
 // =================================================================
 
-const MAX_SOL_PER_TX: u64 = 100_000_000_000_000; // 100,000 SOL max
-const MIN_SOL_PER_TX: u64 = 1; // 1 lamport minimum
+fn check_trade_deadline(deadline_unix_ts: Option<i64>) -> Result<()> {
+    if let Some(deadline) = deadline_unix_ts {
+        require!(Clock::get()?.unix_timestamp <= deadline, BonkError::TradeExpired);
+    }
+
+    Ok(())
+}
 
 // =================================================================
-// ORACLE UPDATE INTERVAL
+// HELPER: Phase-dependent trading fee
+// =================================================================
+// trading_fee_bps is the base (Created) fee. InBattle switches to
+// near_target_fee_bps once sol_collected reaches
+// near_target_threshold_bps of target_sol.
 // =================================================================
 
-const PRICE_UPDATE_INTERVAL: i64 = 86400; // 24 hours
+fn trading_fee_bps_for(state: &TokenBattleState, config: &GlobalConfig) -> u64 {
+    match state.battle_status {
+        BattleStatus::Qualified => config.qualified_fee_bps,
+        BattleStatus::InBattle => {
+            let near_target_sol = (state.target_sol as u128)
+                .saturating_mul(config.near_target_threshold_bps as u128)
+                / 10_000;
+
+            if state.sol_collected as u128 >= near_target_sol {
+                config.near_target_fee_bps
+            } else {
+                config.in_battle_fee_bps
+            }
+        }
+        _ => config.trading_fee_bps,
+    }
+}
 
 // =================================================================
-// VICTORY TOLERANCE - 99.99% for precise Raydium allocation
+// HELPER: Trading fee split (treasury, creator vault, referral vault)
 // =================================================================
 
-const VICTORY_TOLERANCE_BPS: u64 = 9999; // 99.99%
+struct FeeSplit {
+    treasury: u64,
+    creator: u64,
+    referral: u64,
+}
+
+fn split_trading_fee(
+    fee: u64,
+    config: &GlobalConfig,
+    creator: Pubkey,
+    has_referrer: bool,
+) -> Result<FeeSplit> {
+    // Tokens migrated from before creators were recorded have nobody to pay
+    let creator_fee = if creator == Pubkey::default() {
+        0
+    } else {
+        bonk_curve::fee_amount(fee, config.creator_fee_share_bps).map_err(BonkError::from)?
+    };
+
+    let referral_fee = if has_referrer {
+        bonk_curve::fee_amount(fee, config.referral_fee_share_bps).map_err(BonkError::from)?
+    } else {
+        0
+    };
+
+    let treasury_fee = fee
+        .checked_sub(creator_fee)
+        .and_then(|rest| rest.checked_sub(referral_fee))
+        .ok_or(BonkError::MathOverflow)?;
+
+    Ok(FeeSplit {
+        treasury: treasury_fee,
+        creator: creator_fee,
+        referral: referral_fee,
+    })
+}
+
+// Trades on a mint always hit the same shard, so unrelated battles
+// write-lock different fee accounts and can be scheduled in parallel
+fn fee_shard_index(mint: &Pubkey) -> u8 {
+    mint.as_ref()[0] % FEE_SHARD_COUNT
+}
+
+// Book the protocol share of a fee that already landed in the fee shard
+fn accrue_protocol_fee(
+    fee_shard: &mut FeeShard,
+    battle_state: &mut TokenBattleState,
+    amount: u64,
+) -> Result<()> {
+    fee_shard.total_accrued = fee_shard.total_accrued
+        .checked_add(amount)
+        .ok_or(BonkError::MathOverflow)?;
+    battle_state.protocol_fees_accrued = battle_state.protocol_fees_accrued
+        .checked_add(amount)
+        .ok_or(BonkError::MathOverflow)?;
+
+    Ok(())
+}
 
 // =================================================================
-// 🛡️ HELPER: Check if victory conditions are met
+// HELPER: Gladiator mint creation
 // =================================================================
+// Under Token-2022 the mint carries its own name/symbol/uri through
+// the MetadataPointer + TokenMetadata extensions, so wallets can show
+// it. Under the classic token program there is nowhere on the mint to
+// put them, so the battle state keeps them. Returns whether on-mint
+// metadata was written.
+
+fn create_gladiator_mint(
+    accounts: &CreateBattleToken,
+    signer_seeds: &[&[&[u8]]],
+    name: &str,
+    symbol: &str,
+    uri: &str,
+) -> Result<bool> {
+    let mint_authority = accounts.token_battle_state.key();
+    let with_metadata = accounts.token_program.key() == anchor_spl::token_2022::ID;
+
+    let extensions: &[ExtensionType] = if with_metadata {
+        &[ExtensionType::MetadataPointer]
+    } else {
+        &[]
+    };
+    let mint_len = ExtensionType::try_calculate_account_len::<MintState>(extensions)?;
+
+    // TokenMetadata is written after initialization and reallocs the mint,
+    // so its rent has to be in the account up front
+    let metadata_len = if with_metadata {
+        TokenMetadata {
+            update_authority: OptionalNonZeroPubkey::try_from(Some(mint_authority))?,
+            mint: accounts.mint.key(),
+            name: name.to_string(),
+            symbol: symbol.to_string(),
+            uri: uri.to_string(),
+            additional_metadata: vec![],
+        }
+        .tlv_size_of()?
+    } else {
+        0
+    };
 
-fn check_victory_conditions_met(sol_collected: u64, total_volume: u64) -> bool {
-    let sol_threshold = TARGET_SOL
-        .checked_mul(VICTORY_TOLERANCE_BPS)
-        .unwrap_or(0)
-        .checked_div(10000)
-        .unwrap_or(0);
-    
-    sol_collected >= sol_threshold && total_volume >= VICTORY_VOLUME_SOL
+    system_program::create_account(
+        CpiContext::new(
+            accounts.system_program.to_account_info(),
+            system_program::CreateAccount {
+                from: accounts.user.to_account_info(),
+                to: accounts.mint.to_account_info(),
+            },
+        ),
+        Rent::get()?.minimum_balance(mint_len + metadata_len),
+        mint_len as u64,
+        &accounts.token_program.key(),
+    )?;
+
+    if with_metadata {
+        anchor_spl::token_interface::metadata_pointer_initialize(
+            CpiContext::new(
+                accounts.token_program.to_account_info(),
+                anchor_spl::token_interface::MetadataPointerInitialize {
+                    token_program_id: accounts.token_program.to_account_info(),
+                    mint: accounts.mint.to_account_info(),
+                },
+            ),
+            Some(mint_authority),
+            Some(accounts.mint.key()),
+        )?;
+    }
+
+    anchor_spl::token_interface::initialize_mint2(
+        CpiContext::new(
+            accounts.token_program.to_account_info(),
+            anchor_spl::token_interface::InitializeMint2 {
+                mint: accounts.mint.to_account_info(),
+            },
+        ),
+        9,
+        &mint_authority,
+        None,
+    )?;
+
+    if with_metadata {
+        anchor_spl::token_interface::token_metadata_initialize(
+            CpiContext::new_with_signer(
+                accounts.token_program.to_account_info(),
+                anchor_spl::token_interface::TokenMetadataInitialize {
+                    token_program_id: accounts.token_program.to_account_info(),
+                    metadata: accounts.mint.to_account_info(),
+                    update_authority: accounts.token_battle_state.to_account_info(),
+                    mint_authority: accounts.token_battle_state.to_account_info(),
+                    mint: accounts.mint.to_account_info(),
+                },
+                signer_seeds,
+            ),
+            name.to_string(),
+            symbol.to_string(),
+            uri.to_string(),
+        )?;
+    }
+
+    // Pool account: the battle state's ATA for the new mint
+    anchor_spl::associated_token::create(CpiContext::new(
+        accounts.associated_token_program.to_account_info(),
+        anchor_spl::associated_token::Create {
+            payer: accounts.user.to_account_info(),
+            associated_token: accounts.contract_token_account.to_account_info(),
+            authority: accounts.token_battle_state.to_account_info(),
+            mint: accounts.mint.to_account_info(),
+            system_program: accounts.system_program.to_account_info(),
+            token_program: accounts.token_program.to_account_info(),
+        },
+    ))?;
+
+    Ok(with_metadata)
+}
+
+// Drops one of the battle state's authorities over the mint for good
+fn revoke_mint_authority<'info>(
+    token_program: AccountInfo<'info>,
+    mint: AccountInfo<'info>,
+    battle_state: AccountInfo<'info>,
+    signer_seeds: &[&[&[u8]]],
+    authority_type: AuthorityType,
+) -> Result<()> {
+    anchor_spl::token_interface::set_authority(
+        CpiContext::new_with_signer(
+            token_program,
+            anchor_spl::token_interface::SetAuthority {
+                current_authority: battle_state,
+                account_or_mint: mint,
+            },
+            signer_seeds,
+        ),
+        authority_type,
+        None,
+    )
 }
 
 #[program]
@@ -155,1003 +614,3179 @@ pub mod bonk_battle {
     use super::*;
 
     // =================================================================
-    // ORACLE PRICE MANAGEMENT
+    // GLOBAL CONFIG MANAGEMENT
     // =================================================================
 
-    pub fn initialize_price_oracle(
-        ctx: Context<InitializePriceOracle>,
-        initial_sol_price: u64,
+    pub fn initialize_config(
+        ctx: Context<InitializeConfig>,
+        keeper_authority: Pubkey,
+        treasury_wallet: Pubkey,
     ) -> Result<()> {
-        let oracle = &mut ctx.accounts.price_oracle;
-        let current_time = Clock::get()?.unix_timestamp;
+        let config = &mut ctx.accounts.global_config;
+
+        config.admin = ctx.accounts.admin.key();
+        config.keeper_authority = keeper_authority;
+        config.treasury_wallet = treasury_wallet;
+        config.pending_admin = Pubkey::default();
+        config.pending_keeper_authority = Pubkey::default();
+        config.set_default_params();
+        config.paused_until = 0;
+        config.bump = ctx.bumps.global_config;
+        config.version = CONFIG_VERSION;
+
+        emit!(ConfigUpdated {
+            admin: config.admin,
+            keeper_authority: config.keeper_authority,
+            treasury_wallet: config.treasury_wallet,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
 
-        oracle.sol_price_usd = initial_sol_price;
-        oracle.last_update_timestamp = current_time;
-        oracle.next_update_timestamp = current_time + PRICE_UPDATE_INTERVAL;
-        oracle.keeper_authority = ctx.accounts.keeper_authority.key();
-        oracle.update_count = 0;
+        msg!("🔧 Config initialized: keeper = {}, treasury = {}",
+             keeper_authority, treasury_wallet);
 
-        msg!("📊 Oracle initialized: SOL price = ${}.{} USD",
-             initial_sol_price / 1_000_000,
-             (initial_sol_price % 1_000_000) / 10_000);
+        Ok(())
+    }
+
+    // =================================================================
+    // CONFIG MIGRATION
+    // =================================================================
+    // Permissionless, like migrate_battle_state: reallocs the config to
+    // the current layout and fills defaults for fields added since its
+    // version. The payer covers any extra rent.
+    // =================================================================
+
+    pub fn migrate_config(ctx: Context<MigrateConfig>) -> Result<()> {
+        let config_info = ctx.accounts.global_config.to_account_info();
+        let new_len = 8 + GlobalConfig::INIT_SPACE;
+
+        if config_info.data_len() < new_len {
+            let rent_needed = Rent::get()?.minimum_balance(new_len);
+            let top_up = rent_needed.saturating_sub(config_info.lamports());
+
+            if top_up > 0 {
+                system_program::transfer(
+                    CpiContext::new(
+                        ctx.accounts.system_program.to_account_info(),
+                        system_program::Transfer {
+                            from: ctx.accounts.payer.to_account_info(),
+                            to: config_info.clone(),
+                        },
+                    ),
+                    top_up,
+                )?;
+            }
+
+            config_info.realloc(new_len, true)?;
+        }
+
+        let mut config = {
+            let data = config_info.try_borrow_data()?;
+            GlobalConfig::try_deserialize(&mut &data[..])?
+        };
+
+        let from_version = config.version;
+        require!(from_version < CONFIG_VERSION, BonkError::AlreadyMigrated);
+
+        // Every config so far was created at V1, so there are no steps yet.
+        // Each field appended later gets an `if from_version < N` block here.
+
+        config.version = CONFIG_VERSION;
+
+        {
+            let mut data = config_info.try_borrow_mut_data()?;
+            config.try_serialize(&mut &mut data[..])?;
+        }
+
+        emit!(ConfigMigrated {
+            from_version,
+            to_version: CONFIG_VERSION,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+
+        msg!("🔄 Config migrated: v{} → v{}", from_version, CONFIG_VERSION);
 
         Ok(())
     }
 
-    pub fn update_sol_price(
-        ctx: Context<UpdateSolPrice>,
-        new_sol_price: u64,
+    // Keeper and admin keys rotate through propose/accept_authority only
+    pub fn update_config(
+        ctx: Context<UpdateConfig>,
+        new_treasury_wallet: Option<Pubkey>,
     ) -> Result<()> {
-        let oracle = &mut ctx.accounts.price_oracle;
-        let current_time = Clock::get()?.unix_timestamp;
-
-        let previous_price = oracle.sol_price_usd;
+        let config = &mut ctx.accounts.global_config;
 
-        oracle.sol_price_usd = new_sol_price;
-        oracle.last_update_timestamp = current_time;
-        oracle.next_update_timestamp = current_time + PRICE_UPDATE_INTERVAL;
-        oracle.update_count = oracle.update_count.saturating_add(1);
+        if let Some(treasury_wallet) = new_treasury_wallet {
+            config.treasury_wallet = treasury_wallet;
+        }
 
-        emit!(PriceUpdated {
-            previous_price,
-            new_price: new_sol_price,
-            timestamp: current_time,
-            update_number: oracle.update_count,
+        emit!(ConfigUpdated {
+            admin: config.admin,
+            keeper_authority: config.keeper_authority,
+            treasury_wallet: config.treasury_wallet,
+            timestamp: Clock::get()?.unix_timestamp,
         });
 
-        msg!("💹 SOL price updated: ${}.{} → ${}.{} USD",
-             previous_price / 1_000_000, (previous_price % 1_000_000) / 10_000,
-             new_sol_price / 1_000_000, (new_sol_price % 1_000_000) / 10_000);
+        msg!("🔧 Config updated: keeper = {}, treasury = {}",
+             config.keeper_authority, config.treasury_wallet);
 
         Ok(())
     }
 
     // =================================================================
-    // PHASE 1: GLADIATOR CREATION
+    // TWO-STEP AUTHORITY HANDOVER
     // =================================================================
 
-    pub fn create_battle_token(
-        ctx: Context<CreateBattleToken>,
-        name: String,
-        symbol: String,
-        uri: String,
+    pub fn propose_authority(
+        ctx: Context<ProposeAuthority>,
+        role: AuthorityRole,
+        new_authority: Pubkey,
     ) -> Result<()> {
+        require!(new_authority != Pubkey::default(), BonkError::InvalidPendingAuthority);
+
+        let config = &mut ctx.accounts.global_config;
+        let current_authority = config.authority_for(role);
+        require!(new_authority != current_authority, BonkError::InvalidPendingAuthority);
+
+        config.set_pending_authority(role, new_authority);
+
+        emit!(AuthorityTransferProposed {
+            role,
+            current_authority,
+            pending_authority: new_authority,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+
+        msg!("🔑 {:?} handover proposed: {} → {}", role, current_authority, new_authority);
+
+        Ok(())
+    }
+
+    pub fn accept_authority(ctx: Context<AcceptAuthority>, role: AuthorityRole) -> Result<()> {
+        let config = &mut ctx.accounts.global_config;
+        let pending_authority = config.pending_authority_for(role);
+
+        require!(pending_authority != Pubkey::default(), BonkError::NoPendingAuthority);
         require!(
-            name.len() <= 50 && !name.is_empty(),
-            BonkError::InvalidTokenName
-        );
-        require!(
-            symbol.len() <= 10 && !symbol.is_empty(),
-            BonkError::InvalidTokenSymbol
+            ctx.accounts.new_authority.key() == pending_authority,
+            BonkError::InvalidPendingAuthority
         );
-        require!(uri.len() <= 200, BonkError::InvalidTokenUri);
 
-        msg!("🏛️ FORGING GLADIATOR: {} ({})", name, symbol);
-        msg!("⚙️ TIER: {} | Target: {} SOL | Multiplier: 1 BILLION",
-             if USE_TEST_TIER { "TEST" } else { "PRODUCTION" },
-             TARGET_SOL / 1_000_000_000);
+        let previous_authority = config.authority_for(role);
+        config.set_authority(role, pending_authority);
+        config.set_pending_authority(role, Pubkey::default());
 
-        let battle_state_info = ctx.accounts.token_battle_state.to_account_info();
-        let mint_key = ctx.accounts.mint.key();
+        emit!(AuthorityTransferAccepted {
+            role,
+            previous_authority,
+            new_authority: pending_authority,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
 
-        let battle_state = &mut ctx.accounts.token_battle_state;
+        msg!("✅ {:?} handover accepted: {} → {}", role, previous_authority, pending_authority);
 
-        battle_state.mint = mint_key;
-        battle_state.sol_collected = 0;
-        battle_state.tokens_sold = 0;
-        battle_state.total_trade_volume = 0;
-        battle_state.is_active = true;
-        battle_state.battle_status = BattleStatus::Created;
-        battle_state.opponent_mint = Pubkey::default();
-        battle_state.creation_timestamp = Clock::get()?.unix_timestamp;
-        battle_state.last_trade_timestamp = 0;
-        battle_state.battle_start_timestamp = 0;
-        battle_state.victory_timestamp = 0;
-        battle_state.listing_timestamp = 0;
-        battle_state.bump = ctx.bumps.token_battle_state;
-        battle_state.name = name.clone();
-        battle_state.symbol = symbol.clone();
-        battle_state.uri = uri.clone();
+        Ok(())
+    }
 
-        let seeds = &[b"battle_state", mint_key.as_ref(), &[battle_state.bump]];
-        let signer_seeds = &[&seeds[..]];
+    pub fn cancel_authority_transfer(
+        ctx: Context<CancelAuthorityTransfer>,
+        role: AuthorityRole,
+    ) -> Result<()> {
+        let config = &mut ctx.accounts.global_config;
+        let pending_authority = config.pending_authority_for(role);
 
-        anchor_spl::token_interface::mint_to(
-            CpiContext::new_with_signer(
-                ctx.accounts.token_program.to_account_info(),
-                MintTo {
-                    mint: ctx.accounts.mint.to_account_info(),
-                    to: ctx.accounts.contract_token_account.to_account_info(),
-                    authority: battle_state_info,
-                },
-                signer_seeds,
-            ),
-            TOTAL_SUPPLY,
-        )?;
+        require!(pending_authority != Pubkey::default(), BonkError::NoPendingAuthority);
 
-        let sol_price = ctx.accounts.price_oracle.sol_price_usd;
-        let initial_mc_usd = calculate_market_cap_usd_from_sol(0, sol_price)?;
+        config.set_pending_authority(role, Pubkey::default());
 
-        emit!(GladiatorForged {
-            mint: mint_key,
-            creator: ctx.accounts.user.key(),
-            target_sol: TARGET_SOL,
-            victory_volume_sol: VICTORY_VOLUME_SOL,
-            initial_market_cap_usd: initial_mc_usd,
-            sol_price_at_creation: sol_price,
-            is_test_tier: USE_TEST_TIER,
-            timestamp: battle_state.creation_timestamp,
+        emit!(AuthorityTransferCancelled {
+            role,
+            cancelled_authority: pending_authority,
+            timestamp: Clock::get()?.unix_timestamp,
         });
 
-        msg!(
-            "✅ GLADIATOR FORGED! Target: {} SOL | Initial MC: ~${} USD",
-            TARGET_SOL / 1_000_000_000,
-            initial_mc_usd
-        );
+        msg!("❌ {:?} handover to {} cancelled", role, pending_authority);
+
         Ok(())
     }
 
     // =================================================================
-    // PHASE 2: BONDING CURVE TRADING (xy=k)
-    // =================================================================
-    // 🛡️ V4.1 SECURITY: Auto-victory trigger when conditions met
+    // TIMELOCKED PARAMETER CHANGES
     // =================================================================
 
-    pub fn buy_token(ctx: Context<BuyToken>, sol_amount: u64) -> Result<()> {
-        require!(sol_amount >= MIN_SOL_PER_TX, BonkError::AmountTooSmall);
-        require!(sol_amount <= MAX_SOL_PER_TX, BonkError::AmountTooLarge);
-        
-        // 🛡️ SECURITY: Block if already VictoryPending or Listed
-        require!(
-            ctx.accounts.token_battle_state.battle_status != BattleStatus::VictoryPending,
-            BonkError::VictoryAlreadyAchieved
-        );
+    pub fn queue_param_change(
+        ctx: Context<QueueParamChange>,
+        kind: ParamKind,
+        new_value: u64,
+    ) -> Result<()> {
+        let (min_value, max_value) = kind.bounds();
         require!(
-            ctx.accounts.token_battle_state.battle_status != BattleStatus::Listed,
-            BonkError::TokenAlreadyListed
+            new_value >= min_value && new_value <= max_value,
+            BonkError::ParamOutOfBounds
         );
-        require!(
-            ctx.accounts.token_battle_state.is_active,
-            BonkError::TradingInactive
-        );
-
-        // 🛡️ SECURITY: Double-check mathematical conditions even if status not updated
-        // This prevents race conditions where status hasn't been updated yet
-        if ctx.accounts.token_battle_state.battle_status == BattleStatus::InBattle {
-            let already_won = check_victory_conditions_met(
-                ctx.accounts.token_battle_state.sol_collected,
-                ctx.accounts.token_battle_state.total_trade_volume
-            );
-            if already_won {
-                msg!("🏆 Victory conditions already met! No more buys allowed.");
-                return Err(BonkError::VictoryConditionsMet.into());
-            }
-        }
 
-        // Calculate fee first to use NET amount for graduation check
-        let fee_for_check = sol_amount
-            .checked_mul(TRADING_FEE_BPS)
-            .ok_or(BonkError::MathOverflow)?
-            .checked_div(10000)
-            .ok_or(BonkError::MathOverflow)?;
-        let net_amount_for_check = sol_amount
-            .checked_sub(fee_for_check)
+        let current_time = Clock::get()?.unix_timestamp;
+        let activation_timestamp = current_time
+            .checked_add(PARAM_CHANGE_DELAY)
             .ok_or(BonkError::MathOverflow)?;
 
-        let total_sol_after = ctx.accounts.token_battle_state.sol_collected
-            .checked_add(net_amount_for_check)
-            .ok_or(BonkError::MathOverflow)?;
+        // Re-queueing the same kind overwrites the value and restarts the delay
+        let change = &mut ctx.accounts.param_change;
+        change.kind = kind;
+        change.new_value = new_value;
+        change.queued_timestamp = current_time;
+        change.activation_timestamp = activation_timestamp;
+        change.bump = ctx.bumps.param_change;
+
+        emit!(ParamChangeQueued {
+            kind,
+            current_value: ctx.accounts.global_config.param_value(kind),
+            new_value,
+            activation_timestamp,
+        });
 
-        // AUTO-CAP LOGIC - Cap the amount to exactly hit TARGET_SOL
-        let mut actual_sol_amount = sol_amount;
-        let mut was_capped = false;
+        msg!("⏳ {:?} change queued: {} → {} at {}",
+             kind, ctx.accounts.global_config.param_value(kind), new_value, activation_timestamp);
 
-        if total_sol_after > TARGET_SOL {
-            let remaining_capacity = TARGET_SOL
-                .saturating_sub(ctx.accounts.token_battle_state.sol_collected);
+        Ok(())
+    }
 
-            if remaining_capacity == 0 {
-                msg!("🎓 Graduation threshold reached! Trading locked.");
-                return Err(BonkError::WouldExceedGraduation.into());
-            }
+    pub fn execute_param_change(ctx: Context<ExecuteParamChange>, kind: ParamKind) -> Result<()> {
+        let change = &ctx.accounts.param_change;
+        let current_time = Clock::get()?.unix_timestamp;
 
-            // Convert net remaining to gross amount needed
-            actual_sol_amount = remaining_capacity
-                .checked_mul(10000)
-                .ok_or(BonkError::MathOverflow)?
-                .checked_div(10000 - TRADING_FEE_BPS)
-                .ok_or(BonkError::MathOverflow)?;
+        require!(
+            current_time >= change.activation_timestamp,
+            BonkError::ParamChangeNotReady
+        );
 
-            if actual_sol_amount > sol_amount {
-                actual_sol_amount = sol_amount;
-            }
+        let config = &mut ctx.accounts.global_config;
+        let old_value = config.param_value(kind);
+        config.set_param_value(kind, change.new_value);
 
-            if actual_sol_amount < MIN_SOL_PER_TX {
-                msg!("⚠️ Cannot buy: auto-capped amount below minimum");
-                return Err(BonkError::WouldExceedGraduation.into());
-            }
+        emit!(ParamChangeExecuted {
+            kind,
+            old_value,
+            new_value: change.new_value,
+            timestamp: current_time,
+        });
 
-            was_capped = true;
-            msg!("📊 AUTO-CAP: {} → {} lamports", sol_amount, actual_sol_amount);
-        }
+        msg!("✅ {:?} changed: {} → {}", kind, old_value, change.new_value);
 
-        let sol_amount = actual_sol_amount;
+        Ok(())
+    }
 
-        // 🚀 xy=k BONDING CURVE CALCULATION
-        let tokens_to_give = calculate_buy_amount_optimized(
-            sol_amount,
-            ctx.accounts.token_battle_state.sol_collected,
-            ctx.accounts.token_battle_state.tokens_sold,
-        )?;
+    pub fn cancel_param_change(ctx: Context<CancelParamChange>, kind: ParamKind) -> Result<()> {
+        emit!(ParamChangeCancelled {
+            kind,
+            cancelled_value: ctx.accounts.param_change.new_value,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
 
-        require!(tokens_to_give > 0, BonkError::InsufficientOutput);
+        msg!("❌ {:?} change cancelled", kind);
 
-        let fee = sol_amount
-            .checked_mul(TRADING_FEE_BPS)
-            .unwrap()
-            .checked_div(10000)
-            .unwrap();
-        let amount_to_collect = sol_amount.checked_sub(fee).unwrap();
+        Ok(())
+    }
 
-        // Transfer SOL to battle state
-        system_program::transfer(
-            CpiContext::new(
-                ctx.accounts.system_program.to_account_info(),
-                system_program::Transfer {
-                    from: ctx.accounts.user.to_account_info(),
-                    to: ctx.accounts.token_battle_state.to_account_info(),
-                },
-            ),
-            amount_to_collect,
-        )?;
+    // =================================================================
+    // ROLE-BASED ACCESS CONTROL
+    // =================================================================
 
-        // Transfer fee to treasury
-        system_program::transfer(
-            CpiContext::new(
-                ctx.accounts.system_program.to_account_info(),
-                system_program::Transfer {
-                    from: ctx.accounts.user.to_account_info(),
-                    to: ctx.accounts.treasury_wallet.to_account_info(),
-                },
-            ),
-            fee,
-        )?;
+    pub fn initialize_role_registry(ctx: Context<InitializeRoleRegistry>) -> Result<()> {
+        let registry = &mut ctx.accounts.role_registry;
 
-        // Transfer tokens to user
-        let mint_key = ctx.accounts.mint.key();
-        let bump = ctx.accounts.token_battle_state.bump;
-        let seeds = &[b"battle_state", mint_key.as_ref(), &[bump]];
-        let signer_seeds = &[&seeds[..]];
+        registry.members = Vec::new();
+        registry.bump = ctx.bumps.role_registry;
 
-        require!(
-            tokens_to_give <= ctx.accounts.contract_token_account.amount,
-            BonkError::InsufficientLiquidity
-        );
+        msg!("🔧 Role registry initialized");
 
-        anchor_spl::token_interface::transfer_checked(
-            CpiContext::new_with_signer(
-                ctx.accounts.token_program.to_account_info(),
-                anchor_spl::token_interface::TransferChecked {
-                    from: ctx.accounts.contract_token_account.to_account_info(),
-                    to: ctx.accounts.user_token_account.to_account_info(),
-                    authority: ctx.accounts.token_battle_state.to_account_info(),
-                    mint: ctx.accounts.mint.to_account_info(),
-                },
-                signer_seeds,
-            ),
-            tokens_to_give,
-            9,
-        )?;
+        Ok(())
+    }
 
-        // Update state
-        let battle_state = &mut ctx.accounts.token_battle_state;
-        let old_status = battle_state.battle_status.clone();
-        let current_time = Clock::get()?.unix_timestamp;
+    pub fn grant_role(ctx: Context<ManageRole>, role: KeeperRole, member: Pubkey) -> Result<()> {
+        let registry = &mut ctx.accounts.role_registry;
 
-        battle_state.sol_collected = battle_state
-            .sol_collected
-            .checked_add(amount_to_collect)
-            .unwrap();
-        battle_state.tokens_sold = battle_state.tokens_sold.checked_add(tokens_to_give).unwrap();
-        battle_state.total_trade_volume = battle_state
-            .total_trade_volume
-            .checked_add(sol_amount)
-            .unwrap();
-        battle_state.last_trade_timestamp = current_time;
+        require!(!registry.has_role(role, &member), BonkError::RoleAlreadyGranted);
+        require!(registry.members.len() < MAX_ROLE_MEMBERS, BonkError::RoleRegistryFull);
 
-        // SOL-BASED QUALIFICATION CHECK
-        if battle_state.sol_collected >= QUALIFICATION_SOL && old_status == BattleStatus::Created {
-            battle_state.battle_status = BattleStatus::Qualified;
+        registry.members.push(RoleMember { role, member });
 
-            emit!(GladiatorQualified {
-                mint: battle_state.mint,
-                sol_collected: battle_state.sol_collected,
-                qualification_threshold: QUALIFICATION_SOL,
-                timestamp: current_time,
-            });
+        emit!(RoleGranted {
+            role,
+            member,
+            admin: ctx.accounts.admin.key(),
+            timestamp: Clock::get()?.unix_timestamp,
+        });
 
-            msg!("🎯 GLADIATOR QUALIFIED!");
-        }
+        msg!("🔑 {:?} granted to {}", role, member);
 
-        // =================================================================
-        // 🛡️ V4.1 SECURITY: AUTO-VICTORY TRIGGER
-        // If conditions are met during InBattle, auto-lock!
-        // =================================================================
-        if battle_state.battle_status == BattleStatus::InBattle {
-            let victory_achieved = check_victory_conditions_met(
-                battle_state.sol_collected,
-                battle_state.total_trade_volume
-            );
+        Ok(())
+    }
 
-            if victory_achieved {
-                // 🏆 AUTO-VICTORY: Lock trading immediately!
-                battle_state.battle_status = BattleStatus::VictoryPending;
-                battle_state.is_active = false; // CRITICAL: Block ALL trading!
-                battle_state.victory_timestamp = current_time;
-
-                let sol_price = ctx.accounts.price_oracle.sol_price_usd;
-                let final_mc_usd = calculate_market_cap_usd_from_sol(battle_state.sol_collected, sol_price)?;
-                let final_volume_usd = lamports_to_usd(battle_state.total_trade_volume, sol_price)?;
-
-                emit!(VictoryAchieved {
-                    winner_mint: battle_state.mint,
-                    sol_collected: battle_state.sol_collected,
-                    volume_sol: battle_state.total_trade_volume,
-                    target_sol: TARGET_SOL,
-                    victory_volume_sol: VICTORY_VOLUME_SOL,
-                    final_mc_usd,
-                    final_volume_usd,
-                    victory_timestamp: current_time,
-                });
-
-                msg!("🏆🔒 AUTO-VICTORY TRIGGERED! Trading LOCKED!");
-                msg!("   SOL: {}/{} ✅", 
-                     battle_state.sol_collected / 1_000_000_000, 
-                     TARGET_SOL / 1_000_000_000);
-                msg!("   Volume: {}/{} SOL ✅", 
-                     battle_state.total_trade_volume / 1_000_000_000, 
-                     VICTORY_VOLUME_SOL / 1_000_000_000);
-                msg!("   MC: ~${} USD", final_mc_usd);
-                
-                // Return early - no more trading allowed!
-                return Ok(());
-            }
-        }
+    pub fn revoke_role(ctx: Context<ManageRole>, role: KeeperRole, member: Pubkey) -> Result<()> {
+        let registry = &mut ctx.accounts.role_registry;
 
-        // Normal buy logging (only if victory not triggered)
-        let sol_price = ctx.accounts.price_oracle.sol_price_usd;
-        let current_mc_usd = calculate_market_cap_usd_from_sol(battle_state.sol_collected, sol_price)?;
+        let index = registry
+            .members
+            .iter()
+            .position(|entry| entry.role == role && entry.member == member)
+            .ok_or(BonkError::RoleNotGranted)?;
+        registry.members.swap_remove(index);
 
-        emit!(TokenPurchased {
-            mint: battle_state.mint,
-            buyer: ctx.accounts.user.key(),
-            sol_amount,
-            tokens_received: tokens_to_give,
-            sol_collected: battle_state.sol_collected,
-            total_volume_sol: battle_state.total_trade_volume,
-            market_cap_usd: current_mc_usd,
-            sol_price,
+        emit!(RoleRevoked {
+            role,
+            member,
+            admin: ctx.accounts.admin.key(),
+            timestamp: Clock::get()?.unix_timestamp,
         });
 
-        let progress_percent = (battle_state.sol_collected as u128)
-            .checked_mul(100).unwrap()
-            .checked_div(TARGET_SOL as u128).unwrap() as u64;
+        msg!("🔒 {:?} revoked from {}", role, member);
 
-        if was_capped {
-            msg!(
-                "💰 BUY (AUTO-CAPPED): {} tokens for {} SOL | Progress: {}%",
-                tokens_to_give / 1_000_000_000,
-                sol_amount / 1_000_000_000,
-                progress_percent
-            );
-        } else {
-            msg!(
-                "💰 BUY: {} tokens for {} lamports | Progress: {}%",
-                tokens_to_give / 1_000_000_000,
-                sol_amount,
-                progress_percent
-            );
-        }
         Ok(())
     }
 
     // =================================================================
-    // 🛡️ V4.1 SECURITY: SELL WITH VICTORY BLOCK
+    // EMERGENCY PAUSE
     // =================================================================
 
-    pub fn sell_token(ctx: Context<SellToken>, token_amount: u64) -> Result<()> {
-        require!(token_amount > 0, BonkError::AmountTooSmall);
-        
-        // 🛡️ SECURITY CHECK 1: Block if VictoryPending
-        require!(
-            ctx.accounts.token_battle_state.battle_status != BattleStatus::VictoryPending,
-            BonkError::VictoryAlreadyAchieved
-        );
-        
-        // 🛡️ SECURITY CHECK 2: Block if Listed
-        require!(
-            ctx.accounts.token_battle_state.battle_status != BattleStatus::Listed,
-            BonkError::TokenAlreadyListed
-        );
-        
-        // 🛡️ SECURITY CHECK 3: Block if trading inactive
-        require!(
-            ctx.accounts.token_battle_state.is_active,
-            BonkError::TradingInactive
-        );
+    pub fn emergency_pause_all(ctx: Context<EmergencyPauseAll>, duration: i64) -> Result<()> {
+        let config = &mut ctx.accounts.global_config;
+        let current_time = Clock::get()?.unix_timestamp;
 
-        // 🛡️ SECURITY CHECK 4: CRITICAL - Check MATHEMATICAL conditions!
-        // Even if status is still InBattle, block sell if victory conditions are met
-        // This prevents race conditions and exploitation
-        if ctx.accounts.token_battle_state.battle_status == BattleStatus::InBattle {
-            let victory_conditions_met = check_victory_conditions_met(
-                ctx.accounts.token_battle_state.sol_collected,
-                ctx.accounts.token_battle_state.total_trade_volume
-            );
-            
-            if victory_conditions_met {
-                msg!("🛡️ SELL BLOCKED: Victory conditions met!");
-                msg!("   SOL: {} >= {} (threshold)", 
-                     ctx.accounts.token_battle_state.sol_collected,
-                     TARGET_SOL * VICTORY_TOLERANCE_BPS / 10000);
-                msg!("   Volume: {} >= {}", 
-                     ctx.accounts.token_battle_state.total_trade_volume,
-                     VICTORY_VOLUME_SOL);
-                return Err(BonkError::VictoryConditionsMet.into());
-            }
-        }
+        config.paused_until = next_pause_deadline(config.paused_until, current_time, duration)?;
 
-        // 🚀 xy=k BONDING CURVE CALCULATION
-        let sol_to_return = calculate_sell_amount_optimized(
-            token_amount,
-            ctx.accounts.token_battle_state.sol_collected,
-            ctx.accounts.token_battle_state.tokens_sold,
-        )?;
-        
-        require!(sol_to_return > 0, BonkError::InsufficientOutput);
-        require!(
-            ctx.accounts.token_battle_state.sol_collected >= sol_to_return,
-            BonkError::InsufficientLiquidity
-        );
+        emit!(EmergencyPaused {
+            mint: Pubkey::default(),
+            admin: ctx.accounts.admin.key(),
+            paused_until: config.paused_until,
+            timestamp: current_time,
+        });
 
-        // Transfer tokens BACK to contract pool
-        anchor_spl::token_interface::transfer_checked(
-            CpiContext::new(
-                ctx.accounts.token_program.to_account_info(),
-                anchor_spl::token_interface::TransferChecked {
-                    from: ctx.accounts.user_token_account.to_account_info(),
-                    to: ctx.accounts.contract_token_account.to_account_info(),
-                    authority: ctx.accounts.user.to_account_info(),
-                    mint: ctx.accounts.mint.to_account_info(),
-                },
-            ),
-            token_amount,
-            9,
-        )?;
+        msg!("⛔ ALL TRADING PAUSED until {}", config.paused_until);
 
-        // Calculate fees
-        let fee = sol_to_return
-            .checked_mul(TRADING_FEE_BPS)
-            .unwrap()
-            .checked_div(10000)
-            .unwrap();
-        let amount_to_user = sol_to_return.checked_sub(fee).unwrap();
+        Ok(())
+    }
 
-        // Transfer SOL
-        let battle_state_account_info = ctx.accounts.token_battle_state.to_account_info();
+    pub fn emergency_resume_all(ctx: Context<EmergencyPauseAll>) -> Result<()> {
+        let config = &mut ctx.accounts.global_config;
+        let current_time = Clock::get()?.unix_timestamp;
 
-        **battle_state_account_info.try_borrow_mut_lamports()? -= amount_to_user;
-        **ctx.accounts.user.to_account_info().try_borrow_mut_lamports()? += amount_to_user;
+        require!(is_paused(config.paused_until, current_time), BonkError::NotPaused);
+        config.paused_until = current_time;
 
-        **battle_state_account_info.try_borrow_mut_lamports()? -= fee;
-        **ctx.accounts.treasury_wallet.to_account_info().try_borrow_mut_lamports()? += fee;
+        emit!(EmergencyResumed {
+            mint: Pubkey::default(),
+            admin: ctx.accounts.admin.key(),
+            timestamp: current_time,
+        });
 
-        // Update state
+        msg!("✅ ALL TRADING RESUMED");
+
+        Ok(())
+    }
+
+    pub fn emergency_pause(ctx: Context<EmergencyPause>, duration: i64) -> Result<()> {
         let battle_state = &mut ctx.accounts.token_battle_state;
-        battle_state.sol_collected = battle_state
-            .sol_collected
-            .checked_sub(sol_to_return)
-            .unwrap();
-        battle_state.tokens_sold = battle_state
-            .tokens_sold
-            .checked_sub(token_amount)
-            .unwrap();
-        battle_state.total_trade_volume = battle_state
-            .total_trade_volume
-            .checked_add(sol_to_return)
-            .unwrap();
-        battle_state.last_trade_timestamp = Clock::get()?.unix_timestamp;
+        let current_time = Clock::get()?.unix_timestamp;
 
-        // 🛡️ POST-SELL CHECK: If victory conditions now met (due to volume increase), lock!
-        if battle_state.battle_status == BattleStatus::InBattle {
-            let victory_achieved = check_victory_conditions_met(
-                battle_state.sol_collected,
-                battle_state.total_trade_volume
-            );
+        battle_state.paused_until =
+            next_pause_deadline(battle_state.paused_until, current_time, duration)?;
 
-            if victory_achieved {
-                battle_state.battle_status = BattleStatus::VictoryPending;
-                battle_state.is_active = false;
-                battle_state.victory_timestamp = Clock::get()?.unix_timestamp;
+        emit!(EmergencyPaused {
+            mint: battle_state.mint,
+            admin: ctx.accounts.admin.key(),
+            paused_until: battle_state.paused_until,
+            timestamp: current_time,
+        });
 
-                let sol_price = ctx.accounts.price_oracle.sol_price_usd;
-                let final_mc_usd = calculate_market_cap_usd_from_sol(battle_state.sol_collected, sol_price)?;
-                let final_volume_usd = lamports_to_usd(battle_state.total_trade_volume, sol_price)?;
-
-                emit!(VictoryAchieved {
-                    winner_mint: battle_state.mint,
-                    sol_collected: battle_state.sol_collected,
-                    volume_sol: battle_state.total_trade_volume,
-                    target_sol: TARGET_SOL,
-                    victory_volume_sol: VICTORY_VOLUME_SOL,
-                    final_mc_usd,
-                    final_volume_usd,
-                    victory_timestamp: battle_state.victory_timestamp,
-                });
+        msg!("⛔ PAUSED {} until {}", battle_state.mint, battle_state.paused_until);
 
-                msg!("🏆🔒 AUTO-VICTORY TRIGGERED ON SELL! Trading LOCKED!");
-            }
-        }
+        Ok(())
+    }
 
-        let sol_price = ctx.accounts.price_oracle.sol_price_usd;
-        let new_mc_usd = calculate_market_cap_usd_from_sol(battle_state.sol_collected, sol_price)?;
+    pub fn emergency_resume(ctx: Context<EmergencyPause>) -> Result<()> {
+        let battle_state = &mut ctx.accounts.token_battle_state;
+        let current_time = Clock::get()?.unix_timestamp;
 
-        emit!(TokenSold {
+        require!(is_paused(battle_state.paused_until, current_time), BonkError::NotPaused);
+        battle_state.paused_until = current_time;
+
+        emit!(EmergencyResumed {
             mint: battle_state.mint,
-            seller: ctx.accounts.user.key(),
-            token_amount,
-            sol_received: amount_to_user,
-            sol_collected: battle_state.sol_collected,
-            total_volume_sol: battle_state.total_trade_volume,
-            market_cap_usd: new_mc_usd,
-            sol_price,
+            admin: ctx.accounts.admin.key(),
+            timestamp: current_time,
         });
 
-        msg!(
-            "💸 SELL: {} tokens for {} lamports | MC: ${} USD",
-            token_amount / 1_000_000_000,
-            sol_to_return,
-            new_mc_usd
-        );
+        msg!("✅ RESUMED {}", battle_state.mint);
+
         Ok(())
     }
 
     // =================================================================
-    // BATTLE MECHANICS
+    // ORACLE PRICE MANAGEMENT
     // =================================================================
 
-    pub fn start_battle(ctx: Context<StartBattle>) -> Result<()> {
-        msg!("⚔️ BATTLE COMMENCES!");
+    pub fn initialize_price_oracle(
+        ctx: Context<InitializePriceOracle>,
+        initial_sol_price: u64,
+    ) -> Result<()> {
+        let oracle = &mut ctx.accounts.price_oracle;
+        let current_time = Clock::get()?.unix_timestamp;
 
-        let token_a = &mut ctx.accounts.token_a_state;
-        let token_b = &mut ctx.accounts.token_b_state;
+        oracle.sol_price_usd = initial_sol_price;
+        oracle.last_update_timestamp = current_time;
+        oracle.next_update_timestamp = current_time + PRICE_UPDATE_INTERVAL;
+        oracle.keeper_authority = ctx.accounts.keeper_authority.key();
+        oracle.update_count = 0;
 
-        require!(token_a.battle_status == BattleStatus::Qualified, BonkError::NotQualified);
-        require!(token_b.battle_status == BattleStatus::Qualified, BonkError::NotQualified);
-        require!(token_a.mint != token_b.mint, BonkError::SelfBattle);
-        require!(token_a.is_active && token_b.is_active, BonkError::TradingInactive);
+        msg!("📊 Oracle initialized: SOL price = ${}.{} USD",
+             initial_sol_price / 1_000_000,
+             (initial_sol_price % 1_000_000) / 10_000);
 
-        let sol_a = token_a.sol_collected;
-        let sol_b = token_b.sol_collected;
+        Ok(())
+    }
 
-        let sol_diff = if sol_a > sol_b {
-            sol_a - sol_b
-        } else {
-            sol_b - sol_a
-        };
+    pub fn update_sol_price(
+        ctx: Context<UpdateSolPrice>,
+        new_sol_price: u64,
+    ) -> Result<()> {
+        let oracle = &mut ctx.accounts.price_oracle;
+        let current_time = Clock::get()?.unix_timestamp;
 
-        require!(sol_diff <= MATCHMAKING_TOLERANCE_SOL, BonkError::UnfairMatch);
+        let previous_price = oracle.sol_price_usd;
 
-        let battle_timestamp = Clock::get()?.unix_timestamp;
+        oracle.sol_price_usd = new_sol_price;
+        oracle.last_update_timestamp = current_time;
+        oracle.next_update_timestamp = current_time + PRICE_UPDATE_INTERVAL;
+        oracle.update_count = oracle.update_count.saturating_add(1);
 
-        token_a.battle_status = BattleStatus::InBattle;
-        token_a.opponent_mint = token_b.mint;
-        token_a.battle_start_timestamp = battle_timestamp;
-
-        token_b.battle_status = BattleStatus::InBattle;
-        token_b.opponent_mint = token_a.mint;
-        token_b.battle_start_timestamp = battle_timestamp;
-
-        emit!(BattleStarted {
-            token_a: token_a.mint,
-            token_b: token_b.mint,
-            sol_a,
-            sol_b,
-            target_sol: TARGET_SOL,
-            victory_volume_sol: VICTORY_VOLUME_SOL,
-            timestamp: battle_timestamp,
+        emit!(PriceUpdated {
+            previous_price,
+            new_price: new_sol_price,
+            timestamp: current_time,
+            update_number: oracle.update_count,
         });
 
-        msg!("🏟️ BATTLE STARTED! {} vs {} | SOL: {} vs {}",
-             token_a.mint, token_b.mint,
-             sol_a / 1_000_000_000, sol_b / 1_000_000_000);
+        msg!("💹 SOL price updated: ${}.{} → ${}.{} USD",
+             previous_price / 1_000_000, (previous_price % 1_000_000) / 10_000,
+             new_sol_price / 1_000_000, (new_sol_price % 1_000_000) / 10_000);
+
         Ok(())
     }
 
     // =================================================================
-    // VICTORY CHECK - Still available for manual trigger if needed
-    // But auto-victory in buy/sell is the primary mechanism now
+    // PHASE 1: GLADIATOR CREATION
     // =================================================================
 
-    pub fn check_victory_conditions(ctx: Context<CheckVictory>) -> Result<()> {
-        let token_state = &mut ctx.accounts.token_battle_state;
-        let oracle = &ctx.accounts.price_oracle;
+    pub fn create_battle_token(
+        ctx: Context<CreateBattleToken>,
+        name: String,
+        symbol: String,
+        uri: String,
+        tier: u8,
+        initial_buy_sol: Option<u64>,
+    ) -> Result<()> {
+        let tier_params = get_tier_params(tier)?;
 
-        require!(token_state.battle_status == BattleStatus::InBattle, BonkError::NotInBattle);
-        require!(token_state.is_active, BonkError::TradingInactive);
+        require!(
+            name.len() <= 50 && !name.is_empty(),
+            BonkError::InvalidTokenName
+        );
+        require!(
+            symbol.len() <= 10 && !symbol.is_empty(),
+            BonkError::InvalidTokenSymbol
+        );
+        require!(uri.len() <= 200, BonkError::InvalidTokenUri);
 
-        let sol_collected = token_state.sol_collected;
-        let total_volume = token_state.total_trade_volume;
+        msg!("🏛️ FORGING GLADIATOR: {} ({})", name, symbol);
+        msg!("⚙️ TIER: {} | Target: {} SOL | Multiplier: 1 BILLION",
+             if tier == TIER_TEST { "TEST" } else { "PRODUCTION" },
+             tier_params.target_sol / 1_000_000_000);
 
-        let victory_achieved = check_victory_conditions_met(sol_collected, total_volume);
+        let battle_state_info = ctx.accounts.token_battle_state.to_account_info();
+        let mint_key = ctx.accounts.mint.key();
 
-        let sol_price = oracle.sol_price_usd;
-        let final_mc_usd = calculate_market_cap_usd_from_sol(sol_collected, sol_price)?;
-        let final_volume_usd = lamports_to_usd(total_volume, sol_price)?;
+        let seeds = &[b"battle_state", mint_key.as_ref(), &[ctx.bumps.token_battle_state]];
+        let signer_seeds = &[&seeds[..]];
 
-        if victory_achieved {
-            token_state.battle_status = BattleStatus::VictoryPending;
-            token_state.is_active = false; // 🛡️ LOCK trading!
-            token_state.victory_timestamp = Clock::get()?.unix_timestamp;
+        let metadata_on_mint = create_gladiator_mint(ctx.accounts, signer_seeds, &name, &symbol, &uri)?;
+
+        let battle_state = &mut ctx.accounts.token_battle_state;
+
+        battle_state.mint = mint_key;
+        battle_state.sol_collected = 0;
+        battle_state.tokens_sold = 0;
+        battle_state.total_trade_volume = 0;
+        battle_state.battle_volume = 0;
+        battle_state.battle_start_sol = 0;
+        battle_state.battle_start_volume = 0;
+        battle_state.battle_target_sol = 0;
+        battle_state.battle_target_volume = 0;
+        battle_state.is_active = true;
+        battle_state.battle_status = BattleStatus::Created;
+        battle_state.opponent_mint = Pubkey::default();
+        battle_state.creation_timestamp = Clock::get()?.unix_timestamp;
+        battle_state.last_trade_timestamp = 0;
+        battle_state.battle_start_timestamp = 0;
+        battle_state.victory_timestamp = 0;
+        battle_state.listing_timestamp = 0;
+        battle_state.bump = ctx.bumps.token_battle_state;
+        // The mint's TokenMetadata is the record under Token-2022; classic
+        // mints have none, so the strings stay in the battle state
+        if !metadata_on_mint {
+            battle_state.name = name.clone();
+            battle_state.symbol = symbol.clone();
+            battle_state.uri = uri.clone();
+        }
+        battle_state.tier = tier;
+        battle_state.virtual_sol_init = tier_params.virtual_sol_init;
+        battle_state.virtual_token_init = tier_params.virtual_token_init;
+        battle_state.virtual_token_final = tier_params.virtual_token_final;
+        battle_state.constant_k = tier_params.constant_k;
+        battle_state.virtual_sol_reserve = tier_params.virtual_sol_init;
+        battle_state.virtual_token_reserve = tier_params.virtual_token_init;
+        battle_state.target_sol = tier_params.target_sol;
+        battle_state.victory_volume_sol = tier_params.victory_volume_sol;
+        battle_state.qualification_sol = tier_params.qualification_sol;
+        battle_state.paused_until = 0;
+        battle_state.launch_window_end = battle_state.creation_timestamp
+            .checked_add(ctx.accounts.global_config.launch_window_secs as i64)
+            .ok_or(BonkError::MathOverflow)?;
+        battle_state.launch_wallet_cap_sol = ctx.accounts.global_config.launch_wallet_cap_sol;
+        battle_state.creator = ctx.accounts.user.key();
+        battle_state.metadata_on_mint = metadata_on_mint;
+        battle_state.version = BATTLE_STATE_VERSION;
+
+        let creator_vault = &mut ctx.accounts.creator_vault;
+        creator_vault.mint = mint_key;
+        creator_vault.total_accrued = 0;
+        creator_vault.total_claimed = 0;
+        creator_vault.bump = ctx.bumps.creator_vault;
+
+        anchor_spl::token_interface::mint_to(
+            CpiContext::new_with_signer(
+                ctx.accounts.token_program.to_account_info(),
+                MintTo {
+                    mint: ctx.accounts.mint.to_account_info(),
+                    to: ctx.accounts.contract_token_account.to_account_info(),
+                    authority: battle_state_info.clone(),
+                },
+                signer_seeds,
+            ),
+            TOTAL_SUPPLY,
+        )?;
+
+        // 🛡️ FIXED SUPPLY: no mint authority after the one-time mint, and
+        // the mint was created without a freeze authority
+        revoke_mint_authority(
+            ctx.accounts.token_program.to_account_info(),
+            ctx.accounts.mint.to_account_info(),
+            battle_state_info,
+            signer_seeds,
+            AuthorityType::MintTokens,
+        )?;
+
+        emit!(SupplyFinalized {
+            mint: mint_key,
+            total_supply: TOTAL_SUPPLY,
+            timestamp: battle_state.creation_timestamp,
+        });
+
+        let sol_price = ctx.accounts.price_oracle.sol_price_usd;
+        let initial_mc_usd = bonk_curve::market_cap_usd(
+            tier_params.virtual_sol_init,
+            tier_params.virtual_token_init,
+            sol_price,
+            TOTAL_SUPPLY,
+        ).map_err(BonkError::from)?;
+
+        emit!(GladiatorForged {
+            mint: mint_key,
+            creator: ctx.accounts.user.key(),
+            tier,
+            target_sol: tier_params.target_sol,
+            victory_volume_sol: tier_params.victory_volume_sol,
+            initial_market_cap_usd: initial_mc_usd,
+            sol_price_at_creation: sol_price,
+            is_test_tier: tier == TIER_TEST,
+            launch_window_secs: ctx.accounts.global_config.launch_window_secs,
+            launch_window_end: battle_state.launch_window_end,
+            launch_wallet_cap_sol: battle_state.launch_wallet_cap_sol,
+            name,
+            symbol,
+            uri,
+            metadata_on_mint,
+            timestamp: battle_state.creation_timestamp,
+        });
+
+        msg!(
+            "✅ GLADIATOR FORGED! Target: {} SOL | Initial MC: ~${} USD",
+            tier_params.target_sol / 1_000_000_000,
+            initial_mc_usd
+        );
 
-            emit!(VictoryAchieved {
-                winner_mint: token_state.mint,
-                sol_collected,
-                volume_sol: total_volume,
-                target_sol: TARGET_SOL,
-                victory_volume_sol: VICTORY_VOLUME_SOL,
-                final_mc_usd,
-                final_volume_usd,
-                victory_timestamp: token_state.victory_timestamp,
+        // =============================================================
+        // CREATOR DEV-BUY - same curve and fee path as buy_token, in the
+        // same instruction so nobody can buy in between
+        // =============================================================
+        if let Some(sol_amount) = initial_buy_sol {
+            check_buy_allowed(&ctx.accounts.token_battle_state, &ctx.accounts.global_config)?;
+
+            let quote = quote_buy_trade(
+                &ctx.accounts.token_battle_state,
+                &ctx.accounts.global_config,
+                sol_amount,
+                None,
+                ctx.accounts.launch_allocation.as_deref(),
+            )?;
+
+            // 🛡️ ANTI-SNIPE: The launch window opened above, so the
+            // creator's own buy counts against the same per-wallet cap
+            record_launch_buy(
+                &ctx.accounts.token_battle_state,
+                ctx.accounts.launch_allocation.as_mut(),
+                ctx.accounts.user.key(),
+                ctx.bumps.launch_allocation,
+                quote.sol_amount,
+            )?;
+
+            let creator_token_account = ctx
+                .accounts
+                .creator_token_account
+                .as_ref()
+                .ok_or(BonkError::CreatorTokenAccountRequired)?;
+
+            anchor_spl::associated_token::create_idempotent(CpiContext::new(
+                ctx.accounts.associated_token_program.to_account_info(),
+                anchor_spl::associated_token::Create {
+                    payer: ctx.accounts.user.to_account_info(),
+                    associated_token: creator_token_account.to_account_info(),
+                    authority: ctx.accounts.user.to_account_info(),
+                    mint: ctx.accounts.mint.to_account_info(),
+                    system_program: ctx.accounts.system_program.to_account_info(),
+                    token_program: ctx.accounts.token_program.to_account_info(),
+                },
+            ))?;
+
+            let current_time = Clock::get()?.unix_timestamp;
+
+            // Tracked like any buy so a quick dev-sell is caught as a round
+            // trip and the creator's cost basis starts at the dev-buy
+            let trader_activity = ctx
+                .accounts
+                .trader_activity
+                .as_mut()
+                .ok_or(BonkError::TraderActivityRequired)?;
+            let activity_bump = ctx.bumps.trader_activity.unwrap_or(trader_activity.bump);
+            record_trader_activity(
+                trader_activity,
+                mint_key,
+                ctx.accounts.user.key(),
+                activity_bump,
+                TradeSide::Buy,
+                current_time,
+            );
+
+            let user_position = ctx
+                .accounts
+                .user_position
+                .as_mut()
+                .ok_or(BonkError::UserPositionRequired)?;
+            let position_bump = ctx.bumps.user_position.unwrap_or(user_position.bump);
+            record_user_position(
+                user_position,
+                mint_key,
+                ctx.accounts.user.key(),
+                position_bump,
+                TradeSide::Buy,
+                quote.tokens_out,
+                quote.sol_amount,
+            )?;
+
+            // Same settlement as buy_token; the freshly minted pool holds the
+            // whole supply, and a creator's first buy is never a round trip
+            settle_buy_with(
+                BuySettlement {
+                    buyer: ctx.accounts.user.to_account_info(),
+                    buyer_token_account: creator_token_account.to_account_info(),
+                    battle_state: &mut ctx.accounts.token_battle_state,
+                    mint: ctx.accounts.mint.to_account_info(),
+                    contract_token_account: ctx.accounts.contract_token_account.to_account_info(),
+                    pool_token_balance: TOTAL_SUPPLY,
+                    fee_shard: ctx
+                        .accounts
+                        .fee_shard
+                        .as_mut()
+                        .ok_or(BonkError::FeeShardRequired)?,
+                    creator_vault: &mut ctx.accounts.creator_vault,
+                    referral_vault: None,
+                    global_config: &ctx.accounts.global_config,
+                    sol_price: ctx.accounts.price_oracle.sol_price_usd,
+                    counts_toward_battle_volume: true,
+                    system_program: ctx.accounts.system_program.to_account_info(),
+                    token_program: ctx.accounts.token_program.to_account_info(),
+                },
+                quote.sol_amount,
+                quote.fee,
+                quote.fee_bps,
+                quote.net_sol,
+                quote.tokens_out,
+                quote.was_capped,
+            )?;
+
+            let battle_state = &ctx.accounts.token_battle_state;
+
+            emit!(CreatorDevBuy {
+                mint: mint_key,
+                creator: ctx.accounts.user.key(),
+                sol_amount: quote.sol_amount,
+                fee: quote.fee,
+                tokens_received: quote.tokens_out,
+                supply_share_bps: (quote.tokens_out as u128)
+                    .checked_mul(10000)
+                    .ok_or(BonkError::MathOverflow)?
+                    .checked_div(TOTAL_SUPPLY as u128)
+                    .ok_or(BonkError::MathOverflow)? as u64,
+                sol_collected: battle_state.sol_collected,
+                timestamp: current_time,
             });
 
-            msg!("🏆 VICTORY ACHIEVED! Trading LOCKED!");
-            msg!("   SOL Collected: {}/{} ✅",
-                 sol_collected / 1_000_000_000, TARGET_SOL / 1_000_000_000);
-            msg!("   Volume: {}/{} SOL ✅",
-                 total_volume / 1_000_000_000, VICTORY_VOLUME_SOL / 1_000_000_000);
-        } else {
-            msg!("⚔️ Battle continues...");
-            msg!("   SOL: {}/{} ({}%)",
-                 sol_collected / 1_000_000_000,
-                 TARGET_SOL / 1_000_000_000,
-                 (sol_collected as u128 * 100 / TARGET_SOL as u128));
-            msg!("   Volume: {}/{} SOL ({}%)",
-                 total_volume / 1_000_000_000,
-                 VICTORY_VOLUME_SOL / 1_000_000_000,
-                 (total_volume as u128 * 100 / VICTORY_VOLUME_SOL as u128));
+            msg!(
+                "🧑‍💻 CREATOR DEV-BUY: {} tokens for {} lamports",
+                quote.tokens_out / 1_000_000_000,
+                quote.sol_amount
+            );
         }
 
         Ok(())
     }
 
-    pub fn finalize_duel(ctx: Context<FinalizeDuel>) -> Result<()> {
-        msg!("👑 FINALIZING DUEL - WINNER TAKES ALL!");
-
-        let winner_state = &mut ctx.accounts.winner_state;
-        let loser_state = &mut ctx.accounts.loser_state;
+    // =================================================================
+    // STATE MIGRATION
+    // =================================================================
+    // Permissionless: reallocs an old TokenBattleState to the current
+    // layout, fills defaults for fields added since its version, and
+    // bumps the version. The payer covers any extra rent.
+    // =================================================================
 
-        require!(winner_state.battle_status == BattleStatus::VictoryPending, BonkError::NoVictoryAchieved);
-        require!(loser_state.battle_status == BattleStatus::InBattle, BonkError::InvalidBattleState);
-        require!(winner_state.opponent_mint == loser_state.mint, BonkError::NotOpponents);
-        require!(loser_state.opponent_mint == winner_state.mint, BonkError::NotOpponents);
+    pub fn migrate_battle_state(ctx: Context<MigrateBattleState>) -> Result<()> {
+        let battle_state_info = ctx.accounts.token_battle_state.to_account_info();
+        let new_len = 8 + TokenBattleState::INIT_SPACE;
+
+        if battle_state_info.data_len() < new_len {
+            let rent_needed = Rent::get()?.minimum_balance(new_len);
+            let top_up = rent_needed.saturating_sub(battle_state_info.lamports());
+
+            if top_up > 0 {
+                system_program::transfer(
+                    CpiContext::new(
+                        ctx.accounts.system_program.to_account_info(),
+                        system_program::Transfer {
+                            from: ctx.accounts.payer.to_account_info(),
+                            to: battle_state_info.clone(),
+                        },
+                    ),
+                    top_up,
+                )?;
+            }
 
-        // Winner is_active should already be false from auto-victory
-        // But set it again for safety
-        winner_state.is_active = false;
+            battle_state_info.realloc(new_len, true)?;
+        }
 
-        let loser_liquidity = loser_state.sol_collected;
-        let spoils_of_war = loser_liquidity.checked_div(2).unwrap();
+        let mut battle_state = {
+            let data = battle_state_info.try_borrow_data()?;
+            TokenBattleState::try_deserialize(&mut &data[..])?
+        };
 
-        let winner_current = winner_state.sol_collected;
-        let total_after_plunder = winner_current.checked_add(spoils_of_war).unwrap();
-        let platform_fee = total_after_plunder
-            .checked_mul(PLATFORM_FEE_BPS).unwrap()
-            .checked_div(10000).unwrap();
+        let from_version = battle_state.version;
+        require!(from_version < BATTLE_STATE_VERSION, BonkError::AlreadyMigrated);
+
+        // V0 -> V1: every pre-versioning token was created on the test tier
+        if from_version < 1 {
+            let tier_params = get_tier_params(TIER_TEST)?;
+            battle_state.tier = TIER_TEST;
+            battle_state.virtual_sol_init = tier_params.virtual_sol_init;
+            battle_state.virtual_token_init = tier_params.virtual_token_init;
+            battle_state.virtual_token_final = tier_params.virtual_token_final;
+            battle_state.constant_k = tier_params.constant_k;
+            battle_state.target_sol = tier_params.target_sol;
+            battle_state.victory_volume_sol = tier_params.victory_volume_sol;
+            battle_state.qualification_sol = tier_params.qualification_sol;
+            battle_state.paused_until = 0;
+        }
 
-        let keeper_share = platform_fee.checked_mul(80).unwrap().checked_div(100).unwrap();
-        let treasury_share = platform_fee.checked_sub(keeper_share).unwrap();
+        // V1 -> V2: explicit reserves. Tokens follow the real accounting; SOL is
+        // clamped so the pool can always buy back every outstanding token
+        if from_version < 2 {
+            battle_state.virtual_token_reserve = battle_state.virtual_token_init
+                .checked_sub(battle_state.tokens_sold)
+                .ok_or(BonkError::MathOverflow)?;
+            let max_solvent = bonk_curve::max_solvent_virtual_sol(
+                battle_state.sol_collected,
+                battle_state.virtual_token_reserve,
+                battle_state.virtual_token_init,
+            ).map_err(BonkError::from)?;
+            battle_state.virtual_sol_reserve = battle_state.virtual_sol_init
+                .checked_add(battle_state.sol_collected)
+                .ok_or(BonkError::MathOverflow)?
+                .min(max_solvent);
+        }
 
-        let winner_final_sol = total_after_plunder.checked_sub(platform_fee).unwrap();
+        // V2 -> V3: launch windows of existing tokens closed long ago
+        if from_version < 3 {
+            battle_state.launch_window_end = 0;
+            battle_state.launch_wallet_cap_sol = 0;
+        }
 
-        msg!("📊 DUEL MATH:");
-        msg!("   Winner current: {} SOL", winner_current / 1_000_000_000);
-        msg!("   Loser liquidity: {} SOL", loser_liquidity / 1_000_000_000);
-        msg!("   Spoils (50%): {} SOL", spoils_of_war / 1_000_000_000);
-        msg!("   Platform fee (5%): {} SOL", platform_fee / 1_000_000_000);
-        msg!("   Winner final: {} SOL", winner_final_sol / 1_000_000_000);
+        // V3 -> V4: the creator was never stored, so no creator fee share
+        if from_version < 4 {
+            battle_state.creator = Pubkey::default();
+        }
 
-        if spoils_of_war > 0 || platform_fee > 0 {
-            let loser_account = loser_state.to_account_info();
-            let winner_account = winner_state.to_account_info();
-            let keeper_account = ctx.accounts.keeper_authority.to_account_info();
-            let treasury_account = ctx.accounts.treasury_wallet.to_account_info();
+        // V4 -> V5: per-mint protocol fee accounting starts at zero
+        if from_version < 5 {
+            battle_state.protocol_fees_accrued = 0;
+        }
 
-            if spoils_of_war >= platform_fee {
-                let net_to_winner = spoils_of_war.checked_sub(platform_fee).unwrap();
+        // V5 -> V6: no per-trader history exists for older trades, so
+        // battle volume starts from the gross volume so far
+        if from_version < 6 {
+            battle_state.battle_volume = battle_state.total_trade_volume;
+        }
 
-                **loser_account.try_borrow_mut_lamports()? -= spoils_of_war;
-                **winner_account.try_borrow_mut_lamports()? += net_to_winner;
-                **keeper_account.try_borrow_mut_lamports()? += keeper_share;
-                **treasury_account.try_borrow_mut_lamports()? += treasury_share;
-            } else {
-                let winner_contribution = platform_fee.checked_sub(spoils_of_war).unwrap();
+        // V6 -> V7: a battle already running keeps the old absolute rule,
+        // i.e. a zero snapshot with the full threshold and volume as targets
+        if from_version < 7 {
+            battle_state.battle_start_sol = 0;
+            battle_state.battle_start_volume = 0;
+            battle_state.battle_target_sol = battle_state.target_sol
+                .checked_mul(ctx.accounts.global_config.victory_tolerance_bps)
+                .ok_or(BonkError::MathOverflow)?
+                / 10000;
+            battle_state.battle_target_volume = battle_state.victory_volume_sol;
+        }
 
-                **loser_account.try_borrow_mut_lamports()? -= spoils_of_war;
-                **winner_account.try_borrow_mut_lamports()? -= winner_contribution;
-                **keeper_account.try_borrow_mut_lamports()? += keeper_share;
-                **treasury_account.try_borrow_mut_lamports()? += treasury_share;
-            }
+        // V7 -> V8: every older mint is a classic SPL mint with no
+        // TokenMetadata, so the inline name/symbol/uri stay the record
+        if from_version < 8 {
+            battle_state.metadata_on_mint = false;
+        }
 
-            winner_state.sol_collected = winner_final_sol;
-            loser_state.sol_collected = loser_state.sol_collected.checked_sub(spoils_of_war).unwrap();
+        let creator_vault = &mut ctx.accounts.creator_vault;
+        if creator_vault.mint == Pubkey::default() {
+            creator_vault.mint = battle_state.mint;
+            creator_vault.bump = ctx.bumps.creator_vault;
         }
 
-        let finalization_timestamp = Clock::get()?.unix_timestamp;
+        battle_state.version = BATTLE_STATE_VERSION;
 
-        winner_state.battle_status = BattleStatus::Listed;
-        winner_state.listing_timestamp = finalization_timestamp;
-        winner_state.opponent_mint = Pubkey::default();
+        // Strings are stored at their actual length, so hand back the
+        // max_len padding the realloc above added
+        let exact_len = TokenBattleState::space_for(
+            &battle_state.name,
+            &battle_state.symbol,
+            &battle_state.uri,
+        );
+        if battle_state_info.data_len() > exact_len {
+            battle_state_info.realloc(exact_len, false)?;
+        }
 
-        loser_state.battle_status = BattleStatus::Qualified;
-        loser_state.is_active = true;
-        loser_state.opponent_mint = Pubkey::default();
+        {
+            let mut data = battle_state_info.try_borrow_mut_data()?;
+            battle_state.try_serialize(&mut &mut data[..])?;
+        }
 
-        emit!(DuelFinalized {
-            winner_mint: winner_state.mint,
-            loser_mint: loser_state.mint,
-            spoils_transferred: spoils_of_war,
-            platform_fee_collected: platform_fee,
-            total_winner_liquidity: winner_state.sol_collected,
-            loser_remaining_liquidity: loser_state.sol_collected,
-            loser_can_retry: true,
-            timestamp: finalization_timestamp,
+        emit!(BattleStateMigrated {
+            mint: battle_state.mint,
+            name: battle_state.name.clone(),
+            symbol: battle_state.symbol.clone(),
+            uri: battle_state.uri.clone(),
+            from_version,
+            to_version: BATTLE_STATE_VERSION,
+            timestamp: Clock::get()?.unix_timestamp,
         });
 
-        msg!("🎉 DUEL FINALIZED!");
-        msg!("   Winner final liquidity: {} SOL", winner_state.sol_collected / 1_000_000_000);
-        msg!("   Loser remaining: {} SOL (can retry!)", loser_state.sol_collected / 1_000_000_000);
+        msg!("🔄 Battle state migrated: v{} → v{}", from_version, BATTLE_STATE_VERSION);
 
         Ok(())
     }
 
-    pub fn withdraw_for_listing(ctx: Context<WithdrawForListing>) -> Result<()> {
-        require!(
-            ctx.accounts.token_battle_state.battle_status == BattleStatus::Listed,
-            BonkError::NotReadyForListing
+    // =================================================================
+    // PHASE 2: BONDING CURVE TRADING (xy=k)
+    // =================================================================
+    // 🛡️ V4.1 SECURITY: Auto-victory trigger when conditions met
+    // =================================================================
+
+    pub fn buy_token(
+        ctx: Context<BuyToken>,
+        sol_amount: u64,
+        min_tokens_out: u64,
+        deadline_unix_ts: Option<i64>,
+    ) -> Result<()> {
+        check_trade_deadline(deadline_unix_ts)?;
+        check_buy_allowed(&ctx.accounts.token_battle_state, &ctx.accounts.global_config)?;
+
+        let quote = quote_buy_trade(
+            &ctx.accounts.token_battle_state,
+            &ctx.accounts.global_config,
+            sol_amount,
+            Some(&ctx.accounts.trader_activity),
+            ctx.accounts.launch_allocation.as_deref(),
+        )?;
+
+        // 🛡️ SLIPPAGE: Checked on the post-cap amount the user actually gets
+        require!(quote.tokens_out >= min_tokens_out, BonkError::SlippageExceeded);
+
+        record_launch_buy(
+            &ctx.accounts.token_battle_state,
+            ctx.accounts.launch_allocation.as_mut(),
+            ctx.accounts.user.key(),
+            ctx.bumps.launch_allocation,
+            quote.sol_amount,
+        )?;
+        record_trader_activity(
+            &mut ctx.accounts.trader_activity,
+            ctx.accounts.mint.key(),
+            ctx.accounts.user.key(),
+            ctx.bumps.trader_activity,
+            TradeSide::Buy,
+            Clock::get()?.unix_timestamp,
         );
+        record_user_position(
+            &mut ctx.accounts.user_position,
+            ctx.accounts.mint.key(),
+            ctx.accounts.user.key(),
+            ctx.bumps.user_position,
+            TradeSide::Buy,
+            quote.tokens_out,
+            quote.sol_amount,
+        )?;
 
-        let mint_key = ctx.accounts.mint.key();
-        let bump = ctx.accounts.token_battle_state.bump;
-        let tokens_amount = ctx.accounts.contract_token_account.amount;
+        settle_buy(
+            ctx.accounts,
+            quote.sol_amount,
+            quote.fee,
+            quote.fee_bps,
+            quote.net_sol,
+            quote.tokens_out,
+            quote.was_capped,
+        )
+    }
 
-        let battle_state_info = ctx.accounts.token_battle_state.to_account_info();
-        let rent = Rent::get()?.minimum_balance(battle_state_info.data_len());
-        let current_lamports = battle_state_info.lamports();
-        let available_lamports = current_lamports.checked_sub(rent).unwrap_or(0);
+    // =================================================================
+    // EXACT-OUTPUT BUY
+    // =================================================================
+    // Inverts the buy curve so the user pays only the lamports needed
+    // for token_amount (fee included). Same locks as buy_token, but an
+    // exact-output buy cannot be auto-capped, so it fails instead.
+    // =================================================================
 
-        require!(available_lamports > 0, BonkError::NoLiquidityToWithdraw);
+    pub fn buy_exact_tokens(
+        ctx: Context<BuyToken>,
+        token_amount: u64,
+        max_sol_in: u64,
+        deadline_unix_ts: Option<i64>,
+    ) -> Result<()> {
+        check_trade_deadline(deadline_unix_ts)?;
+        require!(token_amount > 0, BonkError::AmountTooSmall);
 
-        let battle_state = &mut ctx.accounts.token_battle_state;
-        battle_state.sol_collected = 0;
+        check_buy_allowed(&ctx.accounts.token_battle_state, &ctx.accounts.global_config)?;
 
-        let seeds = &[b"battle_state", mint_key.as_ref(), &[bump]];
-        let signer_seeds = &[&seeds[..]];
+        let trading_fee_bps = trading_fee_bps_for(
+            &ctx.accounts.token_battle_state,
+            &ctx.accounts.global_config,
+        );
 
-        anchor_spl::token_interface::transfer_checked(
-            CpiContext::new_with_signer(
+        let amount_to_collect = bonk_curve::buy_cost_for_tokens(
+            token_amount,
+            ctx.accounts.token_battle_state.virtual_sol_reserve,
+            ctx.accounts.token_battle_state.virtual_token_reserve,
+            ctx.accounts.token_battle_state.virtual_token_final,
+        ).map_err(BonkError::from)?;
+        let sol_amount = bonk_curve::gross_for_net(amount_to_collect, trading_fee_bps)
+            .map_err(BonkError::from)?;
+        let fee = sol_amount
+            .checked_sub(amount_to_collect)
+            .ok_or(BonkError::MathOverflow)?;
+
+        require!(sol_amount >= MIN_SOL_PER_TX, BonkError::AmountTooSmall);
+        require!(
+            sol_amount <= ctx.accounts.global_config.max_sol_per_tx,
+            BonkError::AmountTooLarge
+        );
+
+        // 🛡️ SLIPPAGE: The full cost, fee included, must fit the user's budget
+        require!(sol_amount <= max_sol_in, BonkError::SlippageExceeded);
+
+        let total_sol_after = ctx.accounts.token_battle_state.sol_collected
+            .checked_add(amount_to_collect)
+            .ok_or(BonkError::MathOverflow)?;
+        if total_sol_after > ctx.accounts.token_battle_state.target_sol {
+            msg!("🎓 Exact buy would pass the graduation threshold");
+            return Err(BonkError::WouldExceedGraduation.into());
+        }
+
+        // Sanity: the forward curve must deliver at least what was priced
+        let tokens_out = bonk_curve::buy_amount(
+            amount_to_collect,
+            ctx.accounts.token_battle_state.virtual_sol_reserve,
+            ctx.accounts.token_battle_state.virtual_token_reserve,
+            ctx.accounts.token_battle_state.virtual_token_final,
+        ).map_err(BonkError::from)?;
+        require!(tokens_out >= token_amount, BonkError::InvalidCurveState);
+
+        record_launch_buy(
+            &ctx.accounts.token_battle_state,
+            ctx.accounts.launch_allocation.as_mut(),
+            ctx.accounts.user.key(),
+            ctx.bumps.launch_allocation,
+            sol_amount,
+        )?;
+        record_trader_activity(
+            &mut ctx.accounts.trader_activity,
+            ctx.accounts.mint.key(),
+            ctx.accounts.user.key(),
+            ctx.bumps.trader_activity,
+            TradeSide::Buy,
+            Clock::get()?.unix_timestamp,
+        );
+        record_user_position(
+            &mut ctx.accounts.user_position,
+            ctx.accounts.mint.key(),
+            ctx.accounts.user.key(),
+            ctx.bumps.user_position,
+            TradeSide::Buy,
+            token_amount,
+            sol_amount,
+        )?;
+
+        settle_buy(
+            ctx.accounts,
+            sol_amount,
+            fee,
+            trading_fee_bps,
+            amount_to_collect,
+            token_amount,
+            false,
+        )
+    }
+
+    // =================================================================
+    // 🛡️ V4.1 SECURITY: SELL WITH VICTORY BLOCK
+    // =================================================================
+
+    pub fn sell_token(
+        ctx: Context<SellToken>,
+        token_amount: u64,
+        min_sol_out: u64,
+        deadline_unix_ts: Option<i64>,
+    ) -> Result<()> {
+        check_trade_deadline(deadline_unix_ts)?;
+
+        check_sell_allowed(&ctx.accounts.token_battle_state, &ctx.accounts.global_config)?;
+
+        let quote = quote_sell_trade(
+            &ctx.accounts.token_battle_state,
+            &ctx.accounts.global_config,
+            token_amount,
+            Some(&ctx.accounts.trader_activity),
+        )?;
+        let sol_to_return = quote.sol_amount;
+        let fee = quote.fee;
+        let fee_bps = quote.fee_bps;
+        let amount_to_user = quote.net_sol;
+
+        // 🛡️ SLIPPAGE: Checked on the net amount after fees
+        require!(amount_to_user >= min_sol_out, BonkError::SlippageExceeded);
+
+        // Transfer tokens BACK to contract pool
+        anchor_spl::token_interface::transfer_checked(
+            CpiContext::new(
                 ctx.accounts.token_program.to_account_info(),
                 anchor_spl::token_interface::TransferChecked {
-                    from: ctx.accounts.contract_token_account.to_account_info(),
-                    to: ctx.accounts.keeper_token_account.to_account_info(),
-                    authority: ctx.accounts.token_battle_state.to_account_info(),
+                    from: ctx.accounts.user_token_account.to_account_info(),
+                    to: ctx.accounts.contract_token_account.to_account_info(),
+                    authority: ctx.accounts.user.to_account_info(),
                     mint: ctx.accounts.mint.to_account_info(),
                 },
-                signer_seeds,
             ),
-            tokens_amount,
+            token_amount,
             9,
         )?;
 
-        let battle_state_info = ctx.accounts.token_battle_state.to_account_info();
-        let keeper_info = ctx.accounts.keeper_authority.to_account_info();
+        // Transfer SOL
+        let battle_state_account_info = ctx.accounts.token_battle_state.to_account_info();
 
-        **battle_state_info.try_borrow_mut_lamports()? = rent;
-        **keeper_info.try_borrow_mut_lamports()? = keeper_info
-            .lamports()
-            .checked_add(available_lamports)
+        **battle_state_account_info.try_borrow_mut_lamports()? -= amount_to_user;
+        **ctx.accounts.user.to_account_info().try_borrow_mut_lamports()? += amount_to_user;
+
+        let fee_split = split_trading_fee(
+            fee,
+            &ctx.accounts.global_config,
+            ctx.accounts.token_battle_state.creator,
+            ctx.accounts.referral_vault.is_some(),
+        )?;
+
+        **battle_state_account_info.try_borrow_mut_lamports()? -= fee;
+        **ctx.accounts.fee_shard.to_account_info().try_borrow_mut_lamports()? += fee_split.treasury;
+        accrue_protocol_fee(
+            &mut ctx.accounts.fee_shard,
+            &mut ctx.accounts.token_battle_state,
+            fee_split.treasury,
+        )?;
+        **ctx.accounts.creator_vault.to_account_info().try_borrow_mut_lamports()? += fee_split.creator;
+        ctx.accounts.creator_vault.total_accrued = ctx.accounts.creator_vault.total_accrued
+            .checked_add(fee_split.creator)
+            .ok_or(BonkError::MathOverflow)?;
+
+        if let Some(referral_vault) = ctx.accounts.referral_vault.as_mut() {
+            **referral_vault.to_account_info().try_borrow_mut_lamports()? += fee_split.referral;
+            referral_vault.total_accrued = referral_vault.total_accrued
+                .checked_add(fee_split.referral)
+                .ok_or(BonkError::MathOverflow)?;
+        }
+
+        let now = Clock::get()?.unix_timestamp;
+        let counts_toward_battle_volume = !is_wash_trade(
+            &ctx.accounts.trader_activity,
+            TradeSide::Sell,
+            now,
+            ctx.accounts.global_config.wash_trade_window_secs,
+        );
+        record_trader_activity(
+            &mut ctx.accounts.trader_activity,
+            ctx.accounts.mint.key(),
+            ctx.accounts.user.key(),
+            ctx.bumps.trader_activity,
+            TradeSide::Sell,
+            now,
+        );
+        record_user_position(
+            &mut ctx.accounts.user_position,
+            ctx.accounts.mint.key(),
+            ctx.accounts.user.key(),
+            ctx.bumps.user_position,
+            TradeSide::Sell,
+            token_amount,
+            amount_to_user,
+        )?;
+
+        // Update state
+        let battle_state = &mut ctx.accounts.token_battle_state;
+        battle_state.sol_collected = battle_state
+            .sol_collected
+            .checked_sub(sol_to_return)
+            .unwrap();
+        battle_state.tokens_sold = battle_state
+            .tokens_sold
+            .checked_sub(token_amount)
+            .unwrap();
+        battle_state.virtual_sol_reserve = battle_state
+            .virtual_sol_reserve
+            .checked_sub(sol_to_return)
             .unwrap();
+        battle_state.virtual_token_reserve = battle_state
+            .virtual_token_reserve
+            .checked_add(token_amount)
+            .unwrap();
+        battle_state.total_trade_volume = battle_state
+            .total_trade_volume
+            .checked_add(sol_to_return)
+            .unwrap();
+        if counts_toward_battle_volume {
+            battle_state.battle_volume = battle_state
+                .battle_volume
+                .checked_add(sol_to_return)
+                .unwrap();
+        }
+        battle_state.last_trade_timestamp = now;
+
+        // 🛡️ POST-SELL CHECK: If victory conditions now met (due to volume increase), lock!
+        if battle_state.battle_status == BattleStatus::InBattle {
+            let victory_achieved = check_victory_conditions_met(
+                battle_state,
+                battle_state.sol_collected,
+                battle_state.battle_volume,
+            );
+
+            if victory_achieved {
+                battle_state.battle_status = BattleStatus::VictoryPending;
+                battle_state.is_active = false;
+                battle_state.victory_timestamp = Clock::get()?.unix_timestamp;
+
+                emit!(victory_achieved_event(battle_state, ctx.accounts.price_oracle.sol_price_usd)?);
+
+                msg!("🏆🔒 AUTO-VICTORY TRIGGERED ON SELL! Trading LOCKED!");
+            }
+        }
+
+        let sol_price = ctx.accounts.price_oracle.sol_price_usd;
+        let new_mc_usd = bonk_curve::market_cap_usd(
+            battle_state.virtual_sol_reserve,
+            battle_state.virtual_token_reserve,
+            sol_price,
+            TOTAL_SUPPLY,
+        ).map_err(BonkError::from)?;
+
+        emit!(TokenSold {
+            mint: battle_state.mint,
+            seller: ctx.accounts.user.key(),
+            token_amount,
+            sol_received: amount_to_user,
+            fee,
+            fee_bps,
+            sol_collected: battle_state.sol_collected,
+            total_volume_sol: battle_state.total_trade_volume,
+            battle_volume_sol: battle_state.battle_volume,
+            market_cap_usd: new_mc_usd,
+            sol_price,
+            referrer: ctx.accounts.referral_vault.as_ref().map(|vault| vault.referrer),
+        });
+
+        msg!(
+            "💸 SELL: {} tokens for {} lamports | MC: ${} USD",
+            token_amount / 1_000_000_000,
+            sol_to_return,
+            new_mc_usd
+        );
+        Ok(())
+    }
+
+    // Returns the rent of a trader's TraderActivity and UserPosition for a
+    // mint. Only once the wash-trade window since their last trade has
+    // passed, so closing cannot hide a round trip; the position history
+    // is gone afterwards and restarts from zero on the next trade.
+    pub fn close_trader_accounts(ctx: Context<CloseTraderAccounts>) -> Result<()> {
+        let activity = &ctx.accounts.trader_activity;
+        let now = Clock::get()?.unix_timestamp;
+        let last_trade = activity.last_buy_timestamp.max(activity.last_sell_timestamp);
+
+        require!(
+            (now.saturating_sub(last_trade) as u64) >= ctx.accounts.global_config.wash_trade_window_secs,
+            BonkError::WashWindowOpen
+        );
+
+        emit!(TraderAccountsClosed {
+            mint: activity.mint,
+            trader: activity.trader,
+            trade_count: ctx.accounts.user_position.trade_count,
+            timestamp: now,
+        });
+
+        msg!("🧹 Trader accounts closed for {}", activity.trader);
+
+        Ok(())
+    }
+
+    // =================================================================
+    // PROTOCOL FEE VAULT
+    // =================================================================
+    // Trades pay the protocol fee into a per-mint fee shard instead of
+    // write-locking the treasury; consolidate_fee_shards gathers shards
+    // into the vault and sweep_fees moves it on, capped per epoch
+    // =================================================================
+
+    pub fn initialize_fee_vault(ctx: Context<InitializeFeeVault>) -> Result<()> {
+        let fee_vault = &mut ctx.accounts.fee_vault;
+
+        fee_vault.total_accrued = 0;
+        fee_vault.total_swept = 0;
+        fee_vault.epoch = Clock::get()?.epoch;
+        fee_vault.epoch_swept = 0;
+        fee_vault.bump = ctx.bumps.fee_vault;
+
+        msg!("🏦 Fee vault initialized");
+
+        Ok(())
+    }
+
+    pub fn initialize_fee_shard(ctx: Context<InitializeFeeShard>, index: u8) -> Result<()> {
+        require!(index < FEE_SHARD_COUNT, BonkError::InvalidFeeShard);
+
+        let fee_shard = &mut ctx.accounts.fee_shard;
+
+        fee_shard.index = index;
+        fee_shard.total_accrued = 0;
+        fee_shard.total_consolidated = 0;
+        fee_shard.bump = ctx.bumps.fee_shard;
+
+        msg!("🏦 Fee shard {} initialized", index);
+
+        Ok(())
+    }
+
+    // Permissionless crank: pass fee shards as remaining accounts
+    pub fn consolidate_fee_shards<'info>(
+        ctx: Context<'_, '_, 'info, 'info, ConsolidateFeeShards<'info>>,
+    ) -> Result<()> {
+        require!(!ctx.remaining_accounts.is_empty(), BonkError::InvalidFeeShard);
+
+        let rent = Rent::get()?;
+        let vault_info = ctx.accounts.fee_vault.to_account_info();
+        let mut consolidated: u64 = 0;
+
+        for shard_info in ctx.remaining_accounts.iter() {
+            require!(shard_info.is_writable, BonkError::InvalidFeeShard);
+
+            let mut fee_shard: Account<FeeShard> = Account::try_from(shard_info)?;
+            let expected = Pubkey::create_program_address(
+                &[FEE_SHARD_SEED, &[fee_shard.index], &[fee_shard.bump]],
+                ctx.program_id,
+            ).map_err(|_| BonkError::InvalidFeeShard)?;
+            require!(shard_info.key() == expected, BonkError::InvalidFeeShard);
+
+            let amount = shard_info.lamports()
+                .saturating_sub(rent.minimum_balance(shard_info.data_len()));
+            if amount == 0 {
+                continue;
+            }
+
+            **shard_info.try_borrow_mut_lamports()? -= amount;
+            **vault_info.try_borrow_mut_lamports()? += amount;
+
+            fee_shard.total_consolidated = fee_shard.total_consolidated
+                .checked_add(amount)
+                .ok_or(BonkError::MathOverflow)?;
+            fee_shard.exit(ctx.program_id)?;
+
+            consolidated = consolidated
+                .checked_add(amount)
+                .ok_or(BonkError::MathOverflow)?;
+        }
+
+        let fee_vault = &mut ctx.accounts.fee_vault;
+        fee_vault.total_accrued = fee_vault.total_accrued
+            .checked_add(consolidated)
+            .ok_or(BonkError::MathOverflow)?;
+
+        emit!(FeeShardsConsolidated {
+            shards: ctx.remaining_accounts.len() as u8,
+            amount: consolidated,
+            total_accrued: fee_vault.total_accrued,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+
+        msg!("🏦 Consolidated {} lamports from {} fee shards", consolidated, ctx.remaining_accounts.len());
+
+        Ok(())
+    }
+
+    pub fn sweep_fees(ctx: Context<SweepFees>, amount: u64) -> Result<()> {
+        let clock = Clock::get()?;
+        let vault_info = ctx.accounts.fee_vault.to_account_info();
+        let rent = Rent::get()?.minimum_balance(vault_info.data_len());
+        let available = vault_info.lamports().saturating_sub(rent);
+
+        require!(amount > 0, BonkError::AmountTooSmall);
+        require!(amount <= available, BonkError::InsufficientLiquidity);
+
+        let fee_vault = &mut ctx.accounts.fee_vault;
+        if clock.epoch != fee_vault.epoch {
+            fee_vault.epoch = clock.epoch;
+            fee_vault.epoch_swept = 0;
+        }
+
+        let epoch_swept = fee_vault.epoch_swept
+            .checked_add(amount)
+            .ok_or(BonkError::MathOverflow)?;
+        let limit = ctx.accounts.global_config.sweep_limit_per_epoch;
+        require!(limit == 0 || epoch_swept <= limit, BonkError::SweepLimitExceeded);
+
+        fee_vault.epoch_swept = epoch_swept;
+        fee_vault.total_swept = fee_vault.total_swept
+            .checked_add(amount)
+            .ok_or(BonkError::MathOverflow)?;
+
+        **vault_info.try_borrow_mut_lamports()? -= amount;
+        **ctx.accounts.treasury_wallet.to_account_info().try_borrow_mut_lamports()? += amount;
+
+        emit!(FeesSwept {
+            treasury: ctx.accounts.treasury_wallet.key(),
+            amount,
+            epoch: fee_vault.epoch,
+            epoch_swept: fee_vault.epoch_swept,
+            total_swept: fee_vault.total_swept,
+            timestamp: clock.unix_timestamp,
+        });
+
+        msg!("🏦 Swept {} lamports to treasury", amount);
+
+        Ok(())
+    }
+
+    // =================================================================
+    // CREATOR FEES
+    // =================================================================
+
+    pub fn claim_creator_fees(ctx: Context<ClaimCreatorFees>) -> Result<()> {
+        let vault_info = ctx.accounts.creator_vault.to_account_info();
+        let rent = Rent::get()?.minimum_balance(vault_info.data_len());
+        let claimable = vault_info.lamports().saturating_sub(rent);
+
+        require!(claimable > 0, BonkError::NothingToClaim);
+
+        **vault_info.try_borrow_mut_lamports()? -= claimable;
+        **ctx.accounts.creator.to_account_info().try_borrow_mut_lamports()? += claimable;
+
+        let creator_vault = &mut ctx.accounts.creator_vault;
+        creator_vault.total_claimed = creator_vault.total_claimed
+            .checked_add(claimable)
+            .ok_or(BonkError::MathOverflow)?;
+
+        emit!(CreatorFeesClaimed {
+            mint: creator_vault.mint,
+            creator: ctx.accounts.creator.key(),
+            amount: claimable,
+            total_claimed: creator_vault.total_claimed,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+
+        msg!("💎 Creator fees claimed: {} lamports", claimable);
+
+        Ok(())
+    }
+
+    // =================================================================
+    // REFERRALS
+    // =================================================================
+    // A referrer registers a vault once; buys and sells that pass it
+    // credit referral_fee_share_bps of the trading fee to it
+    // =================================================================
+
+    pub fn register_referrer(ctx: Context<RegisterReferrer>) -> Result<()> {
+        let referral_vault = &mut ctx.accounts.referral_vault;
+        referral_vault.referrer = ctx.accounts.referrer.key();
+        referral_vault.total_accrued = 0;
+        referral_vault.total_claimed = 0;
+        referral_vault.bump = ctx.bumps.referral_vault;
+
+        msg!("🤝 Referrer registered: {}", referral_vault.referrer);
+
+        Ok(())
+    }
+
+    pub fn claim_referral_rewards(ctx: Context<ClaimReferralRewards>) -> Result<()> {
+        let vault_info = ctx.accounts.referral_vault.to_account_info();
+        let rent = Rent::get()?.minimum_balance(vault_info.data_len());
+        let claimable = vault_info.lamports().saturating_sub(rent);
+
+        require!(claimable > 0, BonkError::NothingToClaim);
+
+        **vault_info.try_borrow_mut_lamports()? -= claimable;
+        **ctx.accounts.referrer.to_account_info().try_borrow_mut_lamports()? += claimable;
+
+        let referral_vault = &mut ctx.accounts.referral_vault;
+        referral_vault.total_claimed = referral_vault.total_claimed
+            .checked_add(claimable)
+            .ok_or(BonkError::MathOverflow)?;
+
+        emit!(ReferralRewardsClaimed {
+            referrer: referral_vault.referrer,
+            amount: claimable,
+            total_claimed: referral_vault.total_claimed,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+
+        msg!("🤝 Referral rewards claimed: {} lamports", claimable);
+
+        Ok(())
+    }
+
+    // =================================================================
+    // READ-ONLY QUOTES
+    // =================================================================
+    // No state changes: the quote is the instruction's return value
+    // (borsh, via set_return_data) so clients can simulateTransaction.
+    // Runs the same checks and math as buy_token / sell_token.
+    // =================================================================
+
+    pub fn quote_buy(ctx: Context<QuoteTrade>, sol_amount: u64) -> Result<BuyQuote> {
+        check_buy_allowed(&ctx.accounts.token_battle_state, &ctx.accounts.global_config)?;
+
+        quote_buy_trade(
+            &ctx.accounts.token_battle_state,
+            &ctx.accounts.global_config,
+            sol_amount,
+            ctx.accounts.trader_activity.as_deref(),
+            ctx.accounts.launch_allocation.as_deref(),
+        )
+    }
+
+    pub fn quote_sell(ctx: Context<QuoteTrade>, token_amount: u64) -> Result<SellQuote> {
+        check_sell_allowed(&ctx.accounts.token_battle_state, &ctx.accounts.global_config)?;
+
+        quote_sell_trade(
+            &ctx.accounts.token_battle_state,
+            &ctx.accounts.global_config,
+            token_amount,
+            ctx.accounts.trader_activity.as_deref(),
+        )
+    }
+
+    // =================================================================
+    // BATTLE MECHANICS
+    // =================================================================
+
+    pub fn start_battle(ctx: Context<StartBattle>) -> Result<()> {
+        msg!("⚔️ BATTLE COMMENCES!");
+
+        let now = Clock::get()?.unix_timestamp;
+        require!(!is_paused(ctx.accounts.global_config.paused_until, now), BonkError::TradingPaused);
+
+        let token_a = &mut ctx.accounts.token_a_state;
+        let token_b = &mut ctx.accounts.token_b_state;
+
+        require!(
+            !is_paused(token_a.paused_until, now) && !is_paused(token_b.paused_until, now),
+            BonkError::TradingPaused
+        );
+
+        require!(token_a.battle_status == BattleStatus::Qualified, BonkError::NotQualified);
+        require!(token_b.battle_status == BattleStatus::Qualified, BonkError::NotQualified);
+        require!(token_a.mint != token_b.mint, BonkError::SelfBattle);
+        require!(token_a.is_active && token_b.is_active, BonkError::TradingInactive);
+        require!(token_a.tier == token_b.tier, BonkError::TierMismatch);
+
+        let sol_a = token_a.sol_collected;
+        let sol_b = token_b.sol_collected;
+
+        let sol_diff = sol_a.abs_diff(sol_b);
+
+        require!(
+            sol_diff <= ctx.accounts.global_config.matchmaking_tolerance_sol,
+            BonkError::UnfairMatch
+        );
+
+        let battle_timestamp = Clock::get()?.unix_timestamp;
+        let (target_sol_a, target_volume_a) = battle_targets(token_a, &ctx.accounts.global_config)?;
+        let (target_sol_b, target_volume_b) = battle_targets(token_b, &ctx.accounts.global_config)?;
+
+        // Snapshot: victory only counts SOL and volume gained from here on
+        token_a.battle_status = BattleStatus::InBattle;
+        token_a.opponent_mint = token_b.mint;
+        token_a.battle_start_timestamp = battle_timestamp;
+        token_a.battle_start_sol = sol_a;
+        token_a.battle_start_volume = token_a.battle_volume;
+        token_a.battle_target_sol = target_sol_a;
+        token_a.battle_target_volume = target_volume_a;
+
+        token_b.battle_status = BattleStatus::InBattle;
+        token_b.opponent_mint = token_a.mint;
+        token_b.battle_start_timestamp = battle_timestamp;
+        token_b.battle_start_sol = sol_b;
+        token_b.battle_start_volume = token_b.battle_volume;
+        token_b.battle_target_sol = target_sol_b;
+        token_b.battle_target_volume = target_volume_b;
+
+        emit!(BattleStarted {
+            token_a: token_a.mint,
+            token_b: token_b.mint,
+            sol_a,
+            sol_b,
+            target_sol: token_a.target_sol,
+            victory_volume_sol: token_a.victory_volume_sol,
+            target_sol_gain_a: target_sol_a,
+            target_sol_gain_b: target_sol_b,
+            target_volume_a,
+            target_volume_b,
+            timestamp: battle_timestamp,
+        });
+
+        msg!("🏟️ BATTLE STARTED! {} vs {} | SOL: {} vs {}",
+             token_a.mint, token_b.mint,
+             sol_a / 1_000_000_000, sol_b / 1_000_000_000);
+        Ok(())
+    }
+
+    // =================================================================
+    // VICTORY CHECK - Still available for manual trigger if needed
+    // But auto-victory in buy/sell is the primary mechanism now
+    // =================================================================
+
+    pub fn check_victory_conditions(ctx: Context<CheckVictory>) -> Result<()> {
+        let token_state = &mut ctx.accounts.token_battle_state;
+        let oracle = &ctx.accounts.price_oracle;
+
+        require!(token_state.battle_status == BattleStatus::InBattle, BonkError::NotInBattle);
+        require!(token_state.is_active, BonkError::TradingInactive);
+
+        let sol_collected = token_state.sol_collected;
+        let battle_volume = token_state.battle_volume;
+
+        let victory_achieved = check_victory_conditions_met(
+            token_state,
+            sol_collected,
+            battle_volume,
+        );
+        let (sol_gained, volume_gained) = battle_gains(token_state, sol_collected, battle_volume);
+        let battle_target_sol = token_state.battle_target_sol;
+        let battle_target_volume = token_state.battle_target_volume;
+
+        if victory_achieved {
+            token_state.battle_status = BattleStatus::VictoryPending;
+            token_state.is_active = false; // 🛡️ LOCK trading!
+            token_state.victory_timestamp = Clock::get()?.unix_timestamp;
+
+            emit!(victory_achieved_event(token_state, oracle.sol_price_usd)?);
+
+            msg!("🏆 VICTORY ACHIEVED! Trading LOCKED!");
+            msg!("   SOL gained: {}/{} ✅",
+                 sol_gained / 1_000_000_000, battle_target_sol / 1_000_000_000);
+            msg!("   Battle volume gained: {}/{} SOL ✅",
+                 volume_gained / 1_000_000_000, battle_target_volume / 1_000_000_000);
+        } else {
+            // A target of 0 is already met, so it reads as 100%
+            msg!("⚔️ Battle continues...");
+            msg!("   SOL gained: {}/{} ({}%)",
+                 sol_gained / 1_000_000_000,
+                 battle_target_sol / 1_000_000_000,
+                 (sol_gained as u128 * 100).checked_div(battle_target_sol as u128).unwrap_or(100));
+            msg!("   Battle volume gained: {}/{} SOL ({}%)",
+                 volume_gained / 1_000_000_000,
+                 battle_target_volume / 1_000_000_000,
+                 (volume_gained as u128 * 100).checked_div(battle_target_volume as u128).unwrap_or(100));
+        }
+
+        Ok(())
+    }
+
+    pub fn finalize_duel(ctx: Context<FinalizeDuel>) -> Result<()> {
+        msg!("👑 FINALIZING DUEL - WINNER TAKES ALL!");
+
+        let now = Clock::get()?.unix_timestamp;
+        require!(!is_paused(ctx.accounts.global_config.paused_until, now), BonkError::TradingPaused);
+
+        let winner_state = &mut ctx.accounts.winner_state;
+        let loser_state = &mut ctx.accounts.loser_state;
+
+        require!(
+            !is_paused(winner_state.paused_until, now) && !is_paused(loser_state.paused_until, now),
+            BonkError::TradingPaused
+        );
+
+        require!(winner_state.battle_status == BattleStatus::VictoryPending, BonkError::NoVictoryAchieved);
+        require!(loser_state.battle_status == BattleStatus::InBattle, BonkError::InvalidBattleState);
+        require!(winner_state.opponent_mint == loser_state.mint, BonkError::NotOpponents);
+        require!(loser_state.opponent_mint == winner_state.mint, BonkError::NotOpponents);
+
+        // Winner is_active should already be false from auto-victory
+        // But set it again for safety
+        winner_state.is_active = false;
+
+        let loser_liquidity = loser_state.sol_collected;
+        let spoils_of_war = loser_liquidity.checked_div(2).unwrap();
+
+        let winner_current = winner_state.sol_collected;
+        let total_after_plunder = winner_current.checked_add(spoils_of_war).unwrap();
+        let platform_fee = total_after_plunder
+            .checked_mul(ctx.accounts.global_config.platform_fee_bps).unwrap()
+            .checked_div(10000).unwrap();
+
+        // Keeper share goes to the configured keeper wallet, not the finalizer hot key
+        let keeper_share = platform_fee.checked_mul(80).unwrap().checked_div(100).unwrap();
+        let treasury_share = platform_fee.checked_sub(keeper_share).unwrap();
+
+        let winner_final_sol = total_after_plunder.checked_sub(platform_fee).unwrap();
+
+        msg!("📊 DUEL MATH:");
+        msg!("   Winner current: {} SOL", winner_current / 1_000_000_000);
+        msg!("   Loser liquidity: {} SOL", loser_liquidity / 1_000_000_000);
+        msg!("   Spoils (50%): {} SOL", spoils_of_war / 1_000_000_000);
+        msg!("   Platform fee (5%): {} SOL", platform_fee / 1_000_000_000);
+        msg!("   Winner final: {} SOL", winner_final_sol / 1_000_000_000);
+
+        if spoils_of_war > 0 || platform_fee > 0 {
+            let loser_account = loser_state.to_account_info();
+            let winner_account = winner_state.to_account_info();
+            let keeper_account = ctx.accounts.keeper_authority.to_account_info();
+            let treasury_account = ctx.accounts.treasury_wallet.to_account_info();
+
+            if spoils_of_war >= platform_fee {
+                let net_to_winner = spoils_of_war.checked_sub(platform_fee).unwrap();
+
+                **loser_account.try_borrow_mut_lamports()? -= spoils_of_war;
+                **winner_account.try_borrow_mut_lamports()? += net_to_winner;
+                **keeper_account.try_borrow_mut_lamports()? += keeper_share;
+                **treasury_account.try_borrow_mut_lamports()? += treasury_share;
+            } else {
+                let winner_contribution = platform_fee.checked_sub(spoils_of_war).unwrap();
+
+                **loser_account.try_borrow_mut_lamports()? -= spoils_of_war;
+                **winner_account.try_borrow_mut_lamports()? -= winner_contribution;
+                **keeper_account.try_borrow_mut_lamports()? += keeper_share;
+                **treasury_account.try_borrow_mut_lamports()? += treasury_share;
+            }
+
+            winner_state.sol_collected = winner_final_sol;
+            loser_state.sol_collected = loser_state.sol_collected.checked_sub(spoils_of_war).unwrap();
+
+            // Loser keeps trading on half the liquidity: pull its virtual SOL
+            // reserve down so holders can never sell back more than remains
+            let max_solvent = bonk_curve::max_solvent_virtual_sol(
+                loser_state.sol_collected,
+                loser_state.virtual_token_reserve,
+                loser_state.virtual_token_init,
+            ).map_err(BonkError::from)?;
+            loser_state.virtual_sol_reserve = loser_state.virtual_sol_reserve.min(max_solvent);
+        }
+
+        let finalization_timestamp = Clock::get()?.unix_timestamp;
+
+        winner_state.battle_status = BattleStatus::Listed;
+        winner_state.listing_timestamp = finalization_timestamp;
+        winner_state.opponent_mint = Pubkey::default();
+
+        loser_state.battle_status = BattleStatus::Qualified;
+        loser_state.is_active = true;
+        loser_state.opponent_mint = Pubkey::default();
+
+        emit!(DuelFinalized {
+            winner_mint: winner_state.mint,
+            loser_mint: loser_state.mint,
+            spoils_transferred: spoils_of_war,
+            platform_fee_collected: platform_fee,
+            total_winner_liquidity: winner_state.sol_collected,
+            loser_remaining_liquidity: loser_state.sol_collected,
+            loser_can_retry: true,
+            timestamp: finalization_timestamp,
+        });
+
+        msg!("🎉 DUEL FINALIZED!");
+        msg!("   Winner final liquidity: {} SOL", winner_state.sol_collected / 1_000_000_000);
+        msg!("   Loser remaining: {} SOL (can retry!)", loser_state.sol_collected / 1_000_000_000);
+
+        Ok(())
+    }
+
+    pub fn withdraw_for_listing(ctx: Context<WithdrawForListing>) -> Result<()> {
+        require!(
+            ctx.accounts.token_battle_state.battle_status == BattleStatus::Listed,
+            BonkError::NotReadyForListing
+        );
+
+        let mint_key = ctx.accounts.mint.key();
+        let bump = ctx.accounts.token_battle_state.bump;
+        let tokens_amount = ctx.accounts.contract_token_account.amount;
+
+        let battle_state_info = ctx.accounts.token_battle_state.to_account_info();
+        let rent = Rent::get()?.minimum_balance(battle_state_info.data_len());
+        let current_lamports = battle_state_info.lamports();
+        let available_lamports = current_lamports.saturating_sub(rent);
+
+        require!(available_lamports > 0, BonkError::NoLiquidityToWithdraw);
+
+        let battle_state = &mut ctx.accounts.token_battle_state;
+        battle_state.sol_collected = 0;
+
+        let seeds = &[b"battle_state", mint_key.as_ref(), &[bump]];
+        let signer_seeds = &[&seeds[..]];
+
+        anchor_spl::token_interface::transfer_checked(
+            CpiContext::new_with_signer(
+                ctx.accounts.token_program.to_account_info(),
+                anchor_spl::token_interface::TransferChecked {
+                    from: ctx.accounts.contract_token_account.to_account_info(),
+                    to: ctx.accounts.keeper_token_account.to_account_info(),
+                    authority: ctx.accounts.token_battle_state.to_account_info(),
+                    mint: ctx.accounts.mint.to_account_info(),
+                },
+                signer_seeds,
+            ),
+            tokens_amount,
+            9,
+        )?;
+
+        // Mints forged before supply was fixed at creation still name the
+        // battle state as mint/freeze authority: revoke both at graduation
+        let battle_state_key = ctx.accounts.token_battle_state.key();
+        let holds_mint_authority = ctx.accounts.mint.mint_authority == COption::Some(battle_state_key);
+        let holds_freeze_authority = ctx.accounts.mint.freeze_authority == COption::Some(battle_state_key);
+
+        if holds_mint_authority {
+            revoke_mint_authority(
+                ctx.accounts.token_program.to_account_info(),
+                ctx.accounts.mint.to_account_info(),
+                ctx.accounts.token_battle_state.to_account_info(),
+                signer_seeds,
+                AuthorityType::MintTokens,
+            )?;
+        }
+        if holds_freeze_authority {
+            revoke_mint_authority(
+                ctx.accounts.token_program.to_account_info(),
+                ctx.accounts.mint.to_account_info(),
+                ctx.accounts.token_battle_state.to_account_info(),
+                signer_seeds,
+                AuthorityType::FreezeAccount,
+            )?;
+        }
+        if holds_mint_authority || holds_freeze_authority {
+            emit!(SupplyFinalized {
+                mint: mint_key,
+                total_supply: ctx.accounts.mint.supply,
+                timestamp: Clock::get()?.unix_timestamp,
+            });
+        }
+
+        let battle_state_info = ctx.accounts.token_battle_state.to_account_info();
+        let keeper_info = ctx.accounts.keeper_authority.to_account_info();
+
+        **battle_state_info.try_borrow_mut_lamports()? = rent;
+        **keeper_info.try_borrow_mut_lamports()? = keeper_info
+            .lamports()
+            .checked_add(available_lamports)
+            .unwrap();
+
+        emit!(ListingWithdrawal {
+            mint: mint_key,
+            sol_withdrawn: available_lamports,
+            tokens_withdrawn: tokens_amount,
+            keeper: ctx.accounts.keeper_authority.key(),
+            lister: ctx.accounts.lister.key(),
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+
+        msg!("📤 WITHDRAWAL FOR LISTING:");
+        msg!("   SOL: {} sent to Keeper", available_lamports / 1_000_000_000);
+        msg!("   Tokens: {} sent to Keeper", tokens_amount / 1_000_000_000);
+
+        Ok(())
+    }
+}
+
+// =================================================================
+// TRADE CHECKS & QUOTES - shared by trades and quote instructions
+// =================================================================
+
+fn check_buy_allowed(state: &TokenBattleState, config: &GlobalConfig) -> Result<()> {
+    // 🛡️ SECURITY: Block while an emergency pause is active
+    let now = Clock::get()?.unix_timestamp;
+    require!(
+        !is_paused(config.paused_until, now)
+            && !is_paused(state.paused_until, now),
+        BonkError::TradingPaused
+    );
+    
+    // 🛡️ SECURITY: Block if already VictoryPending or Listed
+    require!(
+        state.battle_status != BattleStatus::VictoryPending,
+        BonkError::VictoryAlreadyAchieved
+    );
+    require!(
+        state.battle_status != BattleStatus::Listed,
+        BonkError::TokenAlreadyListed
+    );
+    require!(
+        state.is_active,
+        BonkError::TradingInactive
+    );
+
+    // 🛡️ SECURITY: Double-check mathematical conditions even if status not updated
+    // This prevents race conditions where status hasn't been updated yet
+    if state.battle_status == BattleStatus::InBattle {
+        let already_won = check_victory_conditions_met(
+            state,
+            state.sol_collected,
+            state.battle_volume,
+        );
+        if already_won {
+            msg!("🏆 Victory conditions already met! No more buys allowed.");
+            return Err(BonkError::VictoryConditionsMet.into());
+        }
+    }
+
+    Ok(())
+}
+
+fn check_sell_allowed(state: &TokenBattleState, config: &GlobalConfig) -> Result<()> {
+    // 🛡️ SECURITY: Block while an emergency pause is active
+    let now = Clock::get()?.unix_timestamp;
+    require!(
+        !is_paused(config.paused_until, now)
+            && !is_paused(state.paused_until, now),
+        BonkError::TradingPaused
+    );
+    
+    // 🛡️ SECURITY CHECK 1: Block if VictoryPending
+    require!(
+        state.battle_status != BattleStatus::VictoryPending,
+        BonkError::VictoryAlreadyAchieved
+    );
+    
+    // 🛡️ SECURITY CHECK 2: Block if Listed
+    require!(
+        state.battle_status != BattleStatus::Listed,
+        BonkError::TokenAlreadyListed
+    );
+    
+    // 🛡️ SECURITY CHECK 3: Block if trading inactive
+    require!(
+        state.is_active,
+        BonkError::TradingInactive
+    );
+
+    // 🛡️ SECURITY CHECK 4: CRITICAL - Check MATHEMATICAL conditions!
+    // Even if status is still InBattle, block sell if victory conditions are met
+    // This prevents race conditions and exploitation
+    if state.battle_status == BattleStatus::InBattle {
+        let victory_conditions_met = check_victory_conditions_met(
+            state,
+            state.sol_collected,
+            state.battle_volume,
+        );
+        
+        if victory_conditions_met {
+            msg!("🛡️ SELL BLOCKED: Victory conditions met!");
+            msg!("   SOL gained: {} >= {}", 
+                 state.sol_collected.saturating_sub(state.battle_start_sol),
+                 state.battle_target_sol);
+            msg!("   Battle volume gained: {} >= {}", 
+                 state.battle_volume.saturating_sub(state.battle_start_volume),
+                 state.battle_target_volume);
+            return Err(BonkError::VictoryConditionsMet.into());
+        }
+    }
+
+    Ok(())
+}
+
+// Everything buy_token would do before moving funds: bounds, auto-cap,
+// curve and fee. Shared with quote_buy so quotes never drift from trades.
+fn quote_buy_trade(
+    state: &TokenBattleState,
+    config: &GlobalConfig,
+    sol_amount: u64,
+    activity: Option<&TraderActivity>,
+    allocation: Option<&LaunchAllocation>,
+) -> Result<BuyQuote> {
+    let trading_fee_bps = trading_fee_bps_for(state, config);
+
+    require!(sol_amount >= MIN_SOL_PER_TX, BonkError::AmountTooSmall);
+    require!(
+        sol_amount <= config.max_sol_per_tx,
+        BonkError::AmountTooLarge
+    );
+
+    // Calculate fee first to use NET amount for graduation check
+    let fee_for_check = bonk_curve::fee_amount(sol_amount, trading_fee_bps)
+        .map_err(BonkError::from)?;
+    let net_amount_for_check = sol_amount
+        .checked_sub(fee_for_check)
+        .ok_or(BonkError::MathOverflow)?;
+
+    let total_sol_after = state.sol_collected
+        .checked_add(net_amount_for_check)
+        .ok_or(BonkError::MathOverflow)?;
+
+    // AUTO-CAP LOGIC - Cap the amount to exactly hit the tier's target_sol
+    let target_sol = state.target_sol;
+    let mut actual_sol_amount = sol_amount;
+    let mut was_capped = false;
+
+    if total_sol_after > target_sol {
+        let remaining_capacity = target_sol
+            .saturating_sub(state.sol_collected);
+
+        if remaining_capacity == 0 {
+            msg!("🎓 Graduation threshold reached! Trading locked.");
+            return Err(BonkError::WouldExceedGraduation.into());
+        }
+
+        // Convert net remaining to the smallest gross amount that covers it
+        actual_sol_amount = bonk_curve::gross_for_net(remaining_capacity, trading_fee_bps)
+            .map_err(BonkError::from)?
+            .min(sol_amount);
+
+        if actual_sol_amount < MIN_SOL_PER_TX {
+            msg!("⚠️ Cannot buy: auto-capped amount below minimum");
+            return Err(BonkError::WouldExceedGraduation.into());
+        }
+
+        was_capped = true;
+        msg!("📊 AUTO-CAP: {} → {} lamports", sol_amount, actual_sol_amount);
+    }
+
+    let sol_amount = actual_sol_amount;
+    let now = Clock::get()?.unix_timestamp;
+
+    // 🛡️ ANTI-SNIPE: Same cap record_launch_buy enforces; a wallet with
+    // no allocation yet has spent nothing
+    launch_spend_after(
+        state,
+        allocation.map_or(0, |allocation| allocation.sol_spent),
+        sol_amount,
+        now,
+    )?;
+
+    let fee = bonk_curve::fee_amount(sol_amount, trading_fee_bps)
+        .map_err(BonkError::from)?;
+    let net_sol = sol_amount
+        .checked_sub(fee)
+        .ok_or(BonkError::MathOverflow)?;
+
+    // 🚀 xy=k BONDING CURVE CALCULATION - only the net amount enters the pool
+    let tokens_to_give = bonk_curve::buy_amount(
+        net_sol,
+        state.virtual_sol_reserve,
+        state.virtual_token_reserve,
+        state.virtual_token_final,
+    ).map_err(BonkError::from)?;
+
+    require!(tokens_to_give > 0, BonkError::InsufficientOutput);
+
+    let sol_collected_after = state.sol_collected
+        .checked_add(net_sol)
+        .ok_or(BonkError::MathOverflow)?;
+    let battle_volume_after = match activity {
+        Some(activity) if is_wash_trade(activity, TradeSide::Buy, now, config.wash_trade_window_secs) => {
+            state.battle_volume
+        }
+        _ => state.battle_volume
+            .checked_add(sol_amount)
+            .ok_or(BonkError::MathOverflow)?,
+    };
+
+    Ok(BuyQuote {
+        sol_amount,
+        fee,
+        fee_bps: trading_fee_bps,
+        net_sol,
+        tokens_out: tokens_to_give,
+        was_capped,
+        sol_collected_after,
+        triggers_victory: state.battle_status == BattleStatus::InBattle
+            && check_victory_conditions_met(
+                state,
+                sol_collected_after,
+                battle_volume_after,
+            ),
+    })
+}
+
+fn quote_sell_trade(
+    state: &TokenBattleState,
+    config: &GlobalConfig,
+    token_amount: u64,
+    activity: Option<&TraderActivity>,
+) -> Result<SellQuote> {
+    require!(token_amount > 0, BonkError::AmountTooSmall);
+
+    // 🚀 xy=k BONDING CURVE CALCULATION
+    let sol_to_return = bonk_curve::sell_amount(
+        token_amount,
+        state.virtual_sol_reserve,
+        state.virtual_token_reserve,
+    ).map_err(BonkError::from)?;
+    
+    require!(sol_to_return > 0, BonkError::InsufficientOutput);
+    require!(
+        state.sol_collected >= sol_to_return,
+        BonkError::InsufficientLiquidity
+    );
+
+    let trading_fee_bps = trading_fee_bps_for(state, config);
+    let fee = bonk_curve::fee_amount(sol_to_return, trading_fee_bps)
+        .map_err(BonkError::from)?;
+    let net_sol = sol_to_return
+        .checked_sub(fee)
+        .ok_or(BonkError::MathOverflow)?;
+
+    let sol_collected_after = state.sol_collected
+        .checked_sub(sol_to_return)
+        .ok_or(BonkError::MathOverflow)?;
+    let now = Clock::get()?.unix_timestamp;
+    let battle_volume_after = match activity {
+        Some(activity) if is_wash_trade(activity, TradeSide::Sell, now, config.wash_trade_window_secs) => {
+            state.battle_volume
+        }
+        _ => state.battle_volume
+            .checked_add(sol_to_return)
+            .ok_or(BonkError::MathOverflow)?,
+    };
+
+    Ok(SellQuote {
+        sol_amount: sol_to_return,
+        fee,
+        fee_bps: trading_fee_bps,
+        net_sol,
+        sol_collected_after,
+        triggers_victory: state.battle_status == BattleStatus::InBattle
+            && check_victory_conditions_met(
+                state,
+                sol_collected_after,
+                battle_volume_after,
+            ),
+    })
+}
+
+// =================================================================
+// BUY SETTLEMENT - shared by buy_token and buy_exact_tokens
+// =================================================================
+
+// 🛡️ ANTI-SNIPE: While the launch window is open every wallet's
+// cumulative gross spend is capped. Returns the new cumulative spend,
+// or None once the window has closed.
+fn launch_spend_after(
+    state: &TokenBattleState,
+    sol_spent: u64,
+    sol_amount: u64,
+    now: i64,
+) -> Result<Option<u64>> {
+    if now >= state.launch_window_end {
+        return Ok(None);
+    }
+
+    let sol_spent = sol_spent
+        .checked_add(sol_amount)
+        .ok_or(BonkError::MathOverflow)?;
+    require!(
+        sol_spent <= state.launch_wallet_cap_sol,
+        BonkError::LaunchWalletCapExceeded
+    );
+
+    Ok(Some(sol_spent))
+}
+
+// Charges a buy against the wallet's LaunchAllocation while the launch
+// window is open. The creator dev-buy goes through here too.
+fn record_launch_buy(
+    battle_state: &TokenBattleState,
+    allocation: Option<&mut Account<LaunchAllocation>>,
+    buyer: Pubkey,
+    allocation_bump: Option<u8>,
+    sol_amount: u64,
+) -> Result<()> {
+    let now = Clock::get()?.unix_timestamp;
+    if now >= battle_state.launch_window_end {
+        return Ok(());
+    }
+
+    let allocation = allocation.ok_or(BonkError::LaunchAllocationRequired)?;
+
+    let Some(sol_spent) = launch_spend_after(battle_state, allocation.sol_spent, sol_amount, now)? else {
+        return Ok(());
+    };
+
+    allocation.mint = battle_state.mint;
+    allocation.buyer = buyer;
+    allocation.sol_spent = sol_spent;
+    allocation.bump = allocation_bump.unwrap_or(allocation.bump);
+
+    msg!(
+        "🚦 LAUNCH WINDOW: {}/{} lamports used by this wallet",
+        sol_spent,
+        battle_state.launch_wallet_cap_sol
+    );
+
+    Ok(())
+}
+
+// Curve and accounting side of a buy whose funds already moved. Also
+// used by the creator dev-buy in create_battle_token.
+fn record_buy(
+    battle_state: &mut TokenBattleState,
+    sol_amount: u64,
+    amount_to_collect: u64,
+    tokens_to_give: u64,
+    counts_toward_battle_volume: bool,
+    current_time: i64,
+) {
+    let old_status = battle_state.battle_status.clone();
+
+    battle_state.sol_collected = battle_state
+        .sol_collected
+        .checked_add(amount_to_collect)
+        .unwrap();
+    battle_state.tokens_sold = battle_state.tokens_sold.checked_add(tokens_to_give).unwrap();
+    battle_state.virtual_sol_reserve = battle_state
+        .virtual_sol_reserve
+        .checked_add(amount_to_collect)
+        .unwrap();
+    battle_state.virtual_token_reserve = battle_state
+        .virtual_token_reserve
+        .checked_sub(tokens_to_give)
+        .unwrap();
+    battle_state.total_trade_volume = battle_state
+        .total_trade_volume
+        .checked_add(sol_amount)
+        .unwrap();
+    if counts_toward_battle_volume {
+        battle_state.battle_volume = battle_state
+            .battle_volume
+            .checked_add(sol_amount)
+            .unwrap();
+    }
+    battle_state.last_trade_timestamp = current_time;
+
+    // SOL-BASED QUALIFICATION CHECK
+    if battle_state.sol_collected >= battle_state.qualification_sol && old_status == BattleStatus::Created {
+        battle_state.battle_status = BattleStatus::Qualified;
+
+        emit!(GladiatorQualified {
+            mint: battle_state.mint,
+            sol_collected: battle_state.sol_collected,
+            qualification_threshold: battle_state.qualification_sol,
+            timestamp: current_time,
+        });
+
+        msg!("🎯 GLADIATOR QUALIFIED!");
+    }
+}
+
+// Accounts a buy settles against, as plain AccountInfos where possible so
+// buy_token / buy_exact_tokens (BuyToken) and the creator dev-buy
+// (CreateBattleToken) share one path: SOL and fee transfers, token
+// transfer, accounting, TokenPurchased and the auto-victory check
+struct BuySettlement<'a, 'info> {
+    buyer: AccountInfo<'info>,
+    buyer_token_account: AccountInfo<'info>,
+    battle_state: &'a mut Account<'info, TokenBattleState>,
+    mint: AccountInfo<'info>,
+    contract_token_account: AccountInfo<'info>,
+    pool_token_balance: u64,
+    fee_shard: &'a mut Account<'info, FeeShard>,
+    creator_vault: &'a mut Account<'info, CreatorVault>,
+    referral_vault: Option<&'a mut Account<'info, ReferralVault>>,
+    global_config: &'a GlobalConfig,
+    sol_price: u64,
+    counts_toward_battle_volume: bool,
+    system_program: AccountInfo<'info>,
+    token_program: AccountInfo<'info>,
+}
+
+fn settle_buy(
+    accounts: &mut BuyToken,
+    sol_amount: u64,
+    fee: u64,
+    fee_bps: u64,
+    amount_to_collect: u64,
+    tokens_to_give: u64,
+    was_capped: bool,
+) -> Result<()> {
+    let counts_toward_battle_volume = !is_wash_trade(
+        &accounts.trader_activity,
+        TradeSide::Buy,
+        Clock::get()?.unix_timestamp,
+        accounts.global_config.wash_trade_window_secs,
+    );
+
+    settle_buy_with(
+        BuySettlement {
+            buyer: accounts.user.to_account_info(),
+            buyer_token_account: accounts.user_token_account.to_account_info(),
+            pool_token_balance: accounts.contract_token_account.amount,
+            contract_token_account: accounts.contract_token_account.to_account_info(),
+            battle_state: &mut accounts.token_battle_state,
+            mint: accounts.mint.to_account_info(),
+            fee_shard: &mut accounts.fee_shard,
+            creator_vault: &mut accounts.creator_vault,
+            referral_vault: accounts.referral_vault.as_mut(),
+            global_config: &accounts.global_config,
+            sol_price: accounts.price_oracle.sol_price_usd,
+            counts_toward_battle_volume,
+            system_program: accounts.system_program.to_account_info(),
+            token_program: accounts.token_program.to_account_info(),
+        },
+        sol_amount,
+        fee,
+        fee_bps,
+        amount_to_collect,
+        tokens_to_give,
+        was_capped,
+    )
+}
+
+fn settle_buy_with(
+    mut accounts: BuySettlement,
+    sol_amount: u64,
+    fee: u64,
+    fee_bps: u64,
+    amount_to_collect: u64,
+    tokens_to_give: u64,
+    was_capped: bool,
+) -> Result<()> {
+    // Transfer SOL to battle state
+    system_program::transfer(
+        CpiContext::new(
+            accounts.system_program.to_account_info(),
+            system_program::Transfer {
+                from: accounts.buyer.to_account_info(),
+                to: accounts.battle_state.to_account_info(),
+            },
+        ),
+        amount_to_collect,
+    )?;
+
+    // Protocol fee to the fee shard, creator and referral shares to their vaults
+    let fee_split = split_trading_fee(
+        fee,
+        accounts.global_config,
+        accounts.battle_state.creator,
+        accounts.referral_vault.is_some(),
+    )?;
+
+    system_program::transfer(
+        CpiContext::new(
+            accounts.system_program.to_account_info(),
+            system_program::Transfer {
+                from: accounts.buyer.to_account_info(),
+                to: accounts.fee_shard.to_account_info(),
+            },
+        ),
+        fee_split.treasury,
+    )?;
+    accrue_protocol_fee(
+        accounts.fee_shard,
+        accounts.battle_state,
+        fee_split.treasury,
+    )?;
+
+    system_program::transfer(
+        CpiContext::new(
+            accounts.system_program.to_account_info(),
+            system_program::Transfer {
+                from: accounts.buyer.to_account_info(),
+                to: accounts.creator_vault.to_account_info(),
+            },
+        ),
+        fee_split.creator,
+    )?;
+    accounts.creator_vault.total_accrued = accounts.creator_vault.total_accrued
+        .checked_add(fee_split.creator)
+        .ok_or(BonkError::MathOverflow)?;
+
+    if let Some(referral_vault) = accounts.referral_vault.as_mut() {
+        system_program::transfer(
+            CpiContext::new(
+                accounts.system_program.to_account_info(),
+                system_program::Transfer {
+                    from: accounts.buyer.to_account_info(),
+                    to: referral_vault.to_account_info(),
+                },
+            ),
+            fee_split.referral,
+        )?;
+        referral_vault.total_accrued = referral_vault.total_accrued
+            .checked_add(fee_split.referral)
+            .ok_or(BonkError::MathOverflow)?;
+    }
+
+    // Transfer tokens to user
+    let mint_key = accounts.mint.key();
+    let bump = accounts.battle_state.bump;
+    let seeds = &[b"battle_state", mint_key.as_ref(), &[bump]];
+    let signer_seeds = &[&seeds[..]];
+
+    require!(
+        tokens_to_give <= accounts.pool_token_balance,
+        BonkError::InsufficientLiquidity
+    );
+
+    anchor_spl::token_interface::transfer_checked(
+        CpiContext::new_with_signer(
+            accounts.token_program.to_account_info(),
+            anchor_spl::token_interface::TransferChecked {
+                from: accounts.contract_token_account.to_account_info(),
+                to: accounts.buyer_token_account.to_account_info(),
+                authority: accounts.battle_state.to_account_info(),
+                mint: accounts.mint.to_account_info(),
+            },
+            signer_seeds,
+        ),
+        tokens_to_give,
+        9,
+    )?;
+
+    // Update state
+    let battle_state = &mut accounts.battle_state;
+    let current_time = Clock::get()?.unix_timestamp;
+
+    record_buy(
+        battle_state,
+        sol_amount,
+        amount_to_collect,
+        tokens_to_give,
+        accounts.counts_toward_battle_volume,
+        current_time,
+    );
+
+    // Emitted before the victory check so the winning buy is indexed too
+    let sol_price = accounts.sol_price;
+    let current_mc_usd = bonk_curve::market_cap_usd(
+        battle_state.virtual_sol_reserve,
+        battle_state.virtual_token_reserve,
+        sol_price,
+        TOTAL_SUPPLY,
+    ).map_err(BonkError::from)?;
+
+    emit!(TokenPurchased {
+        mint: battle_state.mint,
+        buyer: accounts.buyer.key(),
+        sol_amount,
+        tokens_received: tokens_to_give,
+        fee,
+        fee_bps,
+        sol_collected: battle_state.sol_collected,
+        total_volume_sol: battle_state.total_trade_volume,
+        battle_volume_sol: battle_state.battle_volume,
+        market_cap_usd: current_mc_usd,
+        sol_price,
+        referrer: accounts.referral_vault.as_ref().map(|vault| vault.referrer),
+    });
+
+    // =================================================================
+    // 🛡️ V4.1 SECURITY: AUTO-VICTORY TRIGGER
+    // If conditions are met during InBattle, auto-lock!
+    // =================================================================
+    if battle_state.battle_status == BattleStatus::InBattle {
+        let victory_achieved = check_victory_conditions_met(
+            battle_state,
+            battle_state.sol_collected,
+            battle_state.battle_volume,
+        );
+
+        if victory_achieved {
+            // 🏆 AUTO-VICTORY: Lock trading immediately!
+            battle_state.battle_status = BattleStatus::VictoryPending;
+            battle_state.is_active = false; // CRITICAL: Block ALL trading!
+            battle_state.victory_timestamp = current_time;
+
+            emit!(victory_achieved_event(battle_state, sol_price)?);
+
+            let (sol_gained, volume_gained) = battle_gains(
+                battle_state,
+                battle_state.sol_collected,
+                battle_state.battle_volume,
+            );
+            msg!("🏆🔒 AUTO-VICTORY TRIGGERED! Trading LOCKED!");
+            msg!("   SOL gained: {}/{} ✅", 
+                 sol_gained / 1_000_000_000, 
+                 battle_state.battle_target_sol / 1_000_000_000);
+            msg!("   Battle volume gained: {}/{} SOL ✅", 
+                 volume_gained / 1_000_000_000, 
+                 battle_state.battle_target_volume / 1_000_000_000);
+            msg!("   MC: ~${} USD", current_mc_usd);
+            
+            // Return early - no more trading allowed!
+            return Ok(());
+        }
+    }
+
+    // Normal buy logging (only if victory not triggered)
+    let progress_percent = (battle_state.sol_collected as u128)
+        .checked_mul(100).unwrap()
+        .checked_div(battle_state.target_sol as u128).unwrap() as u64;
+
+    if was_capped {
+        msg!(
+            "💰 BUY (AUTO-CAPPED): {} tokens for {} SOL | Progress: {}%",
+            tokens_to_give / 1_000_000_000,
+            sol_amount / 1_000_000_000,
+            progress_percent
+        );
+    } else {
+        msg!(
+            "💰 BUY: {} tokens for {} lamports | Progress: {}%",
+            tokens_to_give / 1_000_000_000,
+            sol_amount,
+            progress_percent
+        );
+    }
+    Ok(())
+}
+
+// =================================================================
+// ACCOUNT STRUCTURES
+// =================================================================
+
+#[account]
+#[derive(InitSpace)]
+pub struct GlobalConfig {
+    pub admin: Pubkey,
+    pub keeper_authority: Pubkey,
+    pub treasury_wallet: Pubkey,
+    pub bump: u8,
+    pub version: u8,
+    // ---- Appended after V1 ----
+    // New fields only ever go at the end; bump CONFIG_VERSION and give
+    // migrate_config a step that fills them
+    pub pending_admin: Pubkey,
+    pub pending_keeper_authority: Pubkey,
+    pub trading_fee_bps: u64,
+    pub platform_fee_bps: u64,
+    pub matchmaking_tolerance_sol: u64,
+    pub max_sol_per_tx: u64,
+    pub victory_tolerance_bps: u64,
+    pub paused_until: i64,
+    pub launch_window_secs: u64,
+    pub launch_wallet_cap_sol: u64,
+    pub creator_fee_share_bps: u64,
+    pub referral_fee_share_bps: u64,
+    pub qualified_fee_bps: u64,
+    pub in_battle_fee_bps: u64,
+    pub near_target_fee_bps: u64,
+    pub near_target_threshold_bps: u64,
+    pub sweep_limit_per_epoch: u64,
+    pub wash_trade_window_secs: u64,
+    pub battle_volume_target_bps: u64,
+}
+
+impl GlobalConfig {
+    fn set_default_params(&mut self) {
+        self.trading_fee_bps = DEFAULT_TRADING_FEE_BPS;
+        self.platform_fee_bps = DEFAULT_PLATFORM_FEE_BPS;
+        self.matchmaking_tolerance_sol = DEFAULT_MATCHMAKING_TOLERANCE_SOL;
+        self.max_sol_per_tx = DEFAULT_MAX_SOL_PER_TX;
+        self.victory_tolerance_bps = DEFAULT_VICTORY_TOLERANCE_BPS;
+        self.launch_window_secs = DEFAULT_LAUNCH_WINDOW_SECS;
+        self.launch_wallet_cap_sol = DEFAULT_LAUNCH_WALLET_CAP_SOL;
+        self.creator_fee_share_bps = DEFAULT_CREATOR_FEE_SHARE_BPS;
+        self.referral_fee_share_bps = DEFAULT_REFERRAL_FEE_SHARE_BPS;
+        self.qualified_fee_bps = DEFAULT_QUALIFIED_FEE_BPS;
+        self.in_battle_fee_bps = DEFAULT_IN_BATTLE_FEE_BPS;
+        self.near_target_fee_bps = DEFAULT_NEAR_TARGET_FEE_BPS;
+        self.near_target_threshold_bps = DEFAULT_NEAR_TARGET_THRESHOLD_BPS;
+        self.sweep_limit_per_epoch = DEFAULT_SWEEP_LIMIT_PER_EPOCH;
+        self.wash_trade_window_secs = DEFAULT_WASH_TRADE_WINDOW_SECS;
+        self.battle_volume_target_bps = DEFAULT_BATTLE_VOLUME_TARGET_BPS;
+    }
+
+    fn authority_for(&self, role: AuthorityRole) -> Pubkey {
+        match role {
+            AuthorityRole::Admin => self.admin,
+            AuthorityRole::Keeper => self.keeper_authority,
+        }
+    }
+
+    fn set_authority(&mut self, role: AuthorityRole, authority: Pubkey) {
+        match role {
+            AuthorityRole::Admin => self.admin = authority,
+            AuthorityRole::Keeper => self.keeper_authority = authority,
+        }
+    }
+
+    fn pending_authority_for(&self, role: AuthorityRole) -> Pubkey {
+        match role {
+            AuthorityRole::Admin => self.pending_admin,
+            AuthorityRole::Keeper => self.pending_keeper_authority,
+        }
+    }
+
+    fn set_pending_authority(&mut self, role: AuthorityRole, authority: Pubkey) {
+        match role {
+            AuthorityRole::Admin => self.pending_admin = authority,
+            AuthorityRole::Keeper => self.pending_keeper_authority = authority,
+        }
+    }
+
+    fn param_value(&self, kind: ParamKind) -> u64 {
+        match kind {
+            ParamKind::TradingFeeBps => self.trading_fee_bps,
+            ParamKind::PlatformFeeBps => self.platform_fee_bps,
+            ParamKind::MatchmakingToleranceSol => self.matchmaking_tolerance_sol,
+            ParamKind::MaxSolPerTx => self.max_sol_per_tx,
+            ParamKind::VictoryToleranceBps => self.victory_tolerance_bps,
+            ParamKind::LaunchWindowSecs => self.launch_window_secs,
+            ParamKind::LaunchWalletCapSol => self.launch_wallet_cap_sol,
+            ParamKind::CreatorFeeShareBps => self.creator_fee_share_bps,
+            ParamKind::ReferralFeeShareBps => self.referral_fee_share_bps,
+            ParamKind::QualifiedFeeBps => self.qualified_fee_bps,
+            ParamKind::InBattleFeeBps => self.in_battle_fee_bps,
+            ParamKind::NearTargetFeeBps => self.near_target_fee_bps,
+            ParamKind::NearTargetThresholdBps => self.near_target_threshold_bps,
+            ParamKind::SweepLimitPerEpoch => self.sweep_limit_per_epoch,
+            ParamKind::WashTradeWindowSecs => self.wash_trade_window_secs,
+            ParamKind::BattleVolumeTargetBps => self.battle_volume_target_bps,
+        }
+    }
+
+    fn set_param_value(&mut self, kind: ParamKind, value: u64) {
+        match kind {
+            ParamKind::TradingFeeBps => self.trading_fee_bps = value,
+            ParamKind::PlatformFeeBps => self.platform_fee_bps = value,
+            ParamKind::MatchmakingToleranceSol => self.matchmaking_tolerance_sol = value,
+            ParamKind::MaxSolPerTx => self.max_sol_per_tx = value,
+            ParamKind::VictoryToleranceBps => self.victory_tolerance_bps = value,
+            ParamKind::LaunchWindowSecs => self.launch_window_secs = value,
+            ParamKind::LaunchWalletCapSol => self.launch_wallet_cap_sol = value,
+            ParamKind::CreatorFeeShareBps => self.creator_fee_share_bps = value,
+            ParamKind::ReferralFeeShareBps => self.referral_fee_share_bps = value,
+            ParamKind::QualifiedFeeBps => self.qualified_fee_bps = value,
+            ParamKind::InBattleFeeBps => self.in_battle_fee_bps = value,
+            ParamKind::NearTargetFeeBps => self.near_target_fee_bps = value,
+            ParamKind::NearTargetThresholdBps => self.near_target_threshold_bps = value,
+            ParamKind::SweepLimitPerEpoch => self.sweep_limit_per_epoch = value,
+            ParamKind::WashTradeWindowSecs => self.wash_trade_window_secs = value,
+            ParamKind::BattleVolumeTargetBps => self.battle_volume_target_bps = value,
+        }
+    }
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum AuthorityRole {
+    Admin,
+    Keeper,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ParamKind {
+    TradingFeeBps,
+    PlatformFeeBps,
+    MatchmakingToleranceSol,
+    MaxSolPerTx,
+    VictoryToleranceBps,
+    LaunchWindowSecs,
+    LaunchWalletCapSol,
+    CreatorFeeShareBps,
+    ReferralFeeShareBps,
+    QualifiedFeeBps,
+    InBattleFeeBps,
+    NearTargetFeeBps,
+    NearTargetThresholdBps,
+    SweepLimitPerEpoch,
+    WashTradeWindowSecs,
+    BattleVolumeTargetBps,
+}
+
+impl ParamKind {
+    /// Inclusive (min, max) range a queued value must fall in
+    fn bounds(&self) -> (u64, u64) {
+        match self {
+            ParamKind::TradingFeeBps => (0, MAX_TRADING_FEE_BPS),
+            ParamKind::PlatformFeeBps => (0, MAX_PLATFORM_FEE_BPS),
+            ParamKind::MatchmakingToleranceSol => (0, MAX_MATCHMAKING_TOLERANCE_SOL),
+            ParamKind::MaxSolPerTx => (MIN_SOL_PER_TX, DEFAULT_MAX_SOL_PER_TX),
+            ParamKind::VictoryToleranceBps => (MIN_VICTORY_TOLERANCE_BPS, MAX_VICTORY_TOLERANCE_BPS),
+            ParamKind::LaunchWindowSecs => (0, MAX_LAUNCH_WINDOW_SECS),
+            ParamKind::LaunchWalletCapSol => (MIN_SOL_PER_TX, DEFAULT_MAX_SOL_PER_TX),
+            ParamKind::CreatorFeeShareBps => (0, MAX_CREATOR_FEE_SHARE_BPS),
+            ParamKind::ReferralFeeShareBps => (0, MAX_REFERRAL_FEE_SHARE_BPS),
+            ParamKind::QualifiedFeeBps => (0, MAX_TRADING_FEE_BPS),
+            ParamKind::InBattleFeeBps => (0, MAX_TRADING_FEE_BPS),
+            ParamKind::NearTargetFeeBps => (0, MAX_TRADING_FEE_BPS),
+            ParamKind::NearTargetThresholdBps => (0, MAX_NEAR_TARGET_THRESHOLD_BPS),
+            ParamKind::SweepLimitPerEpoch => (0, MAX_SWEEP_LIMIT_PER_EPOCH),
+            ParamKind::WashTradeWindowSecs => (0, MAX_WASH_TRADE_WINDOW_SECS),
+            ParamKind::BattleVolumeTargetBps => (MIN_BATTLE_VOLUME_TARGET_BPS, MAX_BATTLE_VOLUME_TARGET_BPS),
+        }
+    }
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum KeeperRole {
+    OracleUpdater,
+    Matchmaker,
+    Finalizer,
+    Lister,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, PartialEq, Eq, Debug)]
+pub struct RoleMember {
+    pub role: KeeperRole,
+    pub member: Pubkey,
+}
+
+#[account]
+pub struct RoleRegistry {
+    pub members: Vec<RoleMember>,
+    pub bump: u8,
+}
+
+impl RoleRegistry {
+    fn has_role(&self, role: KeeperRole, member: &Pubkey) -> bool {
+        self.members
+            .iter()
+            .any(|entry| entry.role == role && entry.member == *member)
+    }
+}
+
+#[account]
+pub struct PendingParamChange {
+    pub kind: ParamKind,
+    pub new_value: u64,
+    pub queued_timestamp: i64,
+    pub activation_timestamp: i64,
+    pub bump: u8,
+}
+
+#[account]
+pub struct PriceOracle {
+    pub sol_price_usd: u64,
+    pub last_update_timestamp: i64,
+    pub next_update_timestamp: i64,
+    pub keeper_authority: Pubkey,
+    pub update_count: u64,
+}
+
+#[account]
+#[derive(InitSpace)]
+pub struct TokenBattleState {
+    // ---- V0 layout (pre-versioning) ----
+    pub mint: Pubkey,
+    pub sol_collected: u64,
+    pub tokens_sold: u64,
+    pub total_trade_volume: u64,
+    pub is_active: bool,
+    pub battle_status: BattleStatus,
+    pub opponent_mint: Pubkey,
+    pub creation_timestamp: i64,
+    pub last_trade_timestamp: i64,
+    pub battle_start_timestamp: i64,
+    pub victory_timestamp: i64,
+    pub listing_timestamp: i64,
+    pub bump: u8,
+    // Empty for Token-2022 mints, which carry them in TokenMetadata
+    #[max_len(50)]
+    pub name: String,
+    #[max_len(10)]
+    pub symbol: String,
+    #[max_len(200)]
+    pub uri: String,
+    // ---- V1 ----
+    // New fields are only ever appended: an old account realloc'd with zeros
+    // then deserializes with every new field at 0 until the migration fills it
+    pub tier: u8,
+    pub virtual_sol_init: u64,
+    pub virtual_token_init: u64,
+    pub virtual_token_final: u64,
+    // Informational only (launch x * y, see TierParams); kept for the layout
+    pub constant_k: u128,
+    pub target_sol: u64,
+    pub victory_volume_sol: u64,
+    pub qualification_sol: u64,
+    pub paused_until: i64,
+    pub version: u8,
+    pub virtual_sol_reserve: u64,
+    pub virtual_token_reserve: u64,
+    pub launch_window_end: i64,
+    pub launch_wallet_cap_sol: u64,
+    pub creator: Pubkey,
+    pub protocol_fees_accrued: u64,
+    pub battle_volume: u64,
+    pub battle_start_sol: u64,
+    pub battle_start_volume: u64,
+    pub battle_target_sol: u64,
+    pub battle_target_volume: u64,
+    pub metadata_on_mint: bool,
+}
+
+impl TokenBattleState {
+    // Account size with name/symbol/uri stored at their actual length
+    // instead of max_len, so Token-2022 tokens (empty strings) do not pay
+    // rent for metadata that lives on the mint
+    fn space_for(name: &str, symbol: &str, uri: &str) -> usize {
+        8 + TokenBattleState::INIT_SPACE - (50 + 10 + 200) + name.len() + symbol.len() + uri.len()
+    }
+}
+
+#[account]
+pub struct FeeVault {
+    pub total_accrued: u64,
+    pub total_swept: u64,
+    pub epoch: u64,
+    pub epoch_swept: u64,
+    pub bump: u8,
+}
+
+#[account]
+pub struct TraderActivity {
+    pub mint: Pubkey,
+    pub trader: Pubkey,
+    pub last_buy_timestamp: i64,
+    pub last_sell_timestamp: i64,
+    pub bump: u8,
+}
+
+#[account]
+pub struct UserPosition {
+    pub mint: Pubkey,
+    pub user: Pubkey,
+    pub tokens_bought: u64,
+    pub tokens_sold: u64,
+    pub sol_in: u64,  // gross lamports paid, fees included
+    pub sol_out: u64, // net lamports received, after fees
+    pub trade_count: u64,
+    pub first_trade_timestamp: i64,
+    pub last_trade_timestamp: i64,
+    pub bump: u8,
+}
+
+#[account]
+pub struct FeeShard {
+    pub index: u8,
+    pub total_accrued: u64,
+    pub total_consolidated: u64,
+    pub bump: u8,
+}
+
+#[account]
+pub struct ReferralVault {
+    pub referrer: Pubkey,
+    pub total_accrued: u64,
+    pub total_claimed: u64,
+    pub bump: u8,
+}
+
+#[account]
+pub struct CreatorVault {
+    pub mint: Pubkey,
+    pub total_accrued: u64,
+    pub total_claimed: u64,
+    pub bump: u8,
+}
+
+#[account]
+pub struct LaunchAllocation {
+    pub mint: Pubkey,
+    pub buyer: Pubkey,
+    pub sol_spent: u64,
+    pub bump: u8,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, PartialEq, Eq, Debug)]
+pub struct BuyQuote {
+    pub sol_amount: u64,
+    pub fee: u64,
+    pub fee_bps: u64,
+    pub net_sol: u64,
+    pub tokens_out: u64,
+    pub was_capped: bool,
+    pub sol_collected_after: u64,
+    pub triggers_victory: bool,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, PartialEq, Eq, Debug)]
+pub struct SellQuote {
+    pub sol_amount: u64,
+    pub fee: u64,
+    pub fee_bps: u64,
+    pub net_sol: u64,
+    pub sol_collected_after: u64,
+    pub triggers_victory: bool,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, PartialEq, Eq, Debug, InitSpace)]
+pub enum BattleStatus {
+    Created,
+    Qualified,
+    InBattle,
+    VictoryPending,
+    Listed,
+    Defeated,
+}
+
+// =================================================================
+// ACCOUNT CONTEXTS
+// =================================================================
+
+#[derive(Accounts)]
+pub struct InitializeConfig<'info> {
+    #[account(
+        init,
+        payer = admin,
+        space = 8 + GlobalConfig::INIT_SPACE,
+        seeds = [GLOBAL_CONFIG_SEED],
+        bump
+    )]
+    pub global_config: Account<'info, GlobalConfig>,
+
+    #[account(
+        constraint = program.programdata_address()? == Some(program_data.key()) @ BonkError::Unauthorized
+    )]
+    pub program: Program<'info, crate::program::BonkBattle>,
+
+    // Only the upgrade authority can bootstrap the config
+    #[account(
+        constraint = program_data.upgrade_authority_address == Some(admin.key()) @ BonkError::Unauthorized
+    )]
+    pub program_data: Account<'info, ProgramData>,
+
+    #[account(mut)]
+    pub admin: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct UpdateConfig<'info> {
+    #[account(
+        mut,
+        seeds = [GLOBAL_CONFIG_SEED],
+        bump = global_config.bump
+    )]
+    pub global_config: Account<'info, GlobalConfig>,
+
+    #[account(
+        address = global_config.admin @ BonkError::Unauthorized
+    )]
+    pub admin: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct ProposeAuthority<'info> {
+    #[account(
+        mut,
+        seeds = [GLOBAL_CONFIG_SEED],
+        bump = global_config.bump
+    )]
+    pub global_config: Account<'info, GlobalConfig>,
 
-        emit!(ListingWithdrawal {
-            mint: mint_key,
-            sol_withdrawn: available_lamports,
-            tokens_withdrawn: tokens_amount,
-            keeper: ctx.accounts.keeper_authority.key(),
-            timestamp: Clock::get()?.unix_timestamp,
-        });
+    #[account(
+        address = global_config.admin @ BonkError::Unauthorized
+    )]
+    pub admin: Signer<'info>,
+}
 
-        msg!("📤 WITHDRAWAL FOR LISTING:");
-        msg!("   SOL: {} sent to Keeper", available_lamports / 1_000_000_000);
-        msg!("   Tokens: {} sent to Keeper", tokens_amount / 1_000_000_000);
+#[derive(Accounts)]
+pub struct AcceptAuthority<'info> {
+    #[account(
+        mut,
+        seeds = [GLOBAL_CONFIG_SEED],
+        bump = global_config.bump
+    )]
+    pub global_config: Account<'info, GlobalConfig>,
 
-        Ok(())
-    }
+    pub new_authority: Signer<'info>,
 }
 
-// =================================================================
-// HELPER FUNCTIONS
-// =================================================================
+#[derive(Accounts)]
+pub struct CancelAuthorityTransfer<'info> {
+    #[account(
+        mut,
+        seeds = [GLOBAL_CONFIG_SEED],
+        bump = global_config.bump
+    )]
+    pub global_config: Account<'info, GlobalConfig>,
 
-fn calculate_market_cap_usd_from_sol(sol_collected: u64, sol_price_usd: u64) -> Result<u64> {
-    let virtual_sol_init = get_virtual_sol_init();
-    let constant_k = get_constant_k();
+    #[account(
+        address = global_config.admin @ BonkError::Unauthorized
+    )]
+    pub admin: Signer<'info>,
+}
 
-    let current_virtual_sol = (virtual_sol_init as u128)
-        .checked_add(sol_collected as u128)
-        .ok_or(BonkError::MathOverflow)?;
+#[derive(Accounts)]
+#[instruction(kind: ParamKind)]
+pub struct QueueParamChange<'info> {
+    #[account(
+        init_if_needed,
+        payer = admin,
+        space = 8 + 1 + 8 + 8 + 8 + 1,
+        seeds = [PARAM_CHANGE_SEED, &[kind as u8]],
+        bump
+    )]
+    pub param_change: Account<'info, PendingParamChange>,
 
-    let current_virtual_token = constant_k
-        .checked_div(current_virtual_sol)
-        .ok_or(BonkError::MathOverflow)?;
+    #[account(seeds = [GLOBAL_CONFIG_SEED], bump = global_config.bump)]
+    pub global_config: Account<'info, GlobalConfig>,
 
-    let mc_lamports = current_virtual_sol
-        .checked_mul(TOTAL_SUPPLY as u128)
-        .ok_or(BonkError::MathOverflow)?
-        .checked_div(current_virtual_token)
-        .ok_or(BonkError::MathOverflow)? as u64;
+    #[account(
+        mut,
+        address = global_config.admin @ BonkError::Unauthorized
+    )]
+    pub admin: Signer<'info>,
 
-    lamports_to_usd(mc_lamports, sol_price_usd)
+    pub system_program: Program<'info, System>,
 }
 
-fn lamports_to_usd(lamports: u64, sol_price_usd: u64) -> Result<u64> {
-    let usd = (lamports as u128)
-        .checked_mul(sol_price_usd as u128)
-        .ok_or(BonkError::MathOverflow)?
-        .checked_div(1_000_000_000_u128)
-        .ok_or(BonkError::MathOverflow)?
-        .checked_div(1_000_000_u128)
-        .ok_or(BonkError::MathOverflow)? as u64;
+#[derive(Accounts)]
+#[instruction(kind: ParamKind)]
+pub struct ExecuteParamChange<'info> {
+    #[account(
+        mut,
+        close = admin,
+        seeds = [PARAM_CHANGE_SEED, &[kind as u8]],
+        bump = param_change.bump
+    )]
+    pub param_change: Account<'info, PendingParamChange>,
+
+    #[account(
+        mut,
+        seeds = [GLOBAL_CONFIG_SEED],
+        bump = global_config.bump
+    )]
+    pub global_config: Account<'info, GlobalConfig>,
 
-    Ok(usd)
+    #[account(
+        mut,
+        address = global_config.admin @ BonkError::Unauthorized
+    )]
+    pub admin: Signer<'info>,
 }
 
-// =================================================================
-// BONDING CURVE xy=k CALCULATIONS
-// =================================================================
+#[derive(Accounts)]
+#[instruction(kind: ParamKind)]
+pub struct CancelParamChange<'info> {
+    #[account(
+        mut,
+        close = admin,
+        seeds = [PARAM_CHANGE_SEED, &[kind as u8]],
+        bump = param_change.bump
+    )]
+    pub param_change: Account<'info, PendingParamChange>,
 
-fn calculate_buy_amount_optimized(
-    sol_amount: u64,
-    sol_already_collected: u64,
-    _tokens_already_sold: u64,
-) -> Result<u64> {
-    let virtual_sol_init = get_virtual_sol_init();
-    let constant_k = get_constant_k();
-    let virtual_token_final = get_virtual_token_final();
-
-    let current_virtual_sol = (virtual_sol_init as u128)
-        .checked_add(sol_already_collected as u128)
-        .ok_or(BonkError::MathOverflow)?;
+    #[account(seeds = [GLOBAL_CONFIG_SEED], bump = global_config.bump)]
+    pub global_config: Account<'info, GlobalConfig>,
 
-    let current_virtual_token = constant_k
-        .checked_div(current_virtual_sol)
-        .ok_or(BonkError::MathOverflow)?;
+    #[account(
+        mut,
+        address = global_config.admin @ BonkError::Unauthorized
+    )]
+    pub admin: Signer<'info>,
+}
 
-    if current_virtual_token <= virtual_token_final as u128 {
-        return Ok(0);
-    }
+#[derive(Accounts)]
+pub struct InitializeFeeVault<'info> {
+    #[account(
+        init,
+        payer = admin,
+        space = 8 + 8 + 8 + 8 + 8 + 1,
+        seeds = [FEE_VAULT_SEED],
+        bump
+    )]
+    pub fee_vault: Account<'info, FeeVault>,
 
-    let new_virtual_sol = current_virtual_sol
-        .checked_add(sol_amount as u128)
-        .ok_or(BonkError::MathOverflow)?;
+    #[account(seeds = [GLOBAL_CONFIG_SEED], bump = global_config.bump)]
+    pub global_config: Account<'info, GlobalConfig>,
 
-    let new_virtual_token = constant_k
-        .checked_div(new_virtual_sol)
-        .ok_or(BonkError::MathOverflow)?;
+    #[account(
+        mut,
+        address = global_config.admin @ BonkError::Unauthorized
+    )]
+    pub admin: Signer<'info>,
 
-    let tokens_out = current_virtual_token
-        .checked_sub(new_virtual_token)
-        .ok_or(BonkError::MathOverflow)?;
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(index: u8)]
+pub struct InitializeFeeShard<'info> {
+    #[account(
+        init,
+        payer = admin,
+        space = 8 + 1 + 8 + 8 + 1,
+        seeds = [FEE_SHARD_SEED, &[index]],
+        bump
+    )]
+    pub fee_shard: Account<'info, FeeShard>,
 
-    let max_tokens = current_virtual_token
-        .checked_sub(virtual_token_final as u128)
-        .unwrap_or(0);
+    #[account(seeds = [GLOBAL_CONFIG_SEED], bump = global_config.bump)]
+    pub global_config: Account<'info, GlobalConfig>,
 
-    let final_tokens = if tokens_out > max_tokens {
-        max_tokens
-    } else {
-        tokens_out
-    };
+    #[account(
+        mut,
+        address = global_config.admin @ BonkError::Unauthorized
+    )]
+    pub admin: Signer<'info>,
 
-    Ok(final_tokens as u64)
+    pub system_program: Program<'info, System>,
 }
 
-fn calculate_sell_amount_optimized(
-    token_amount: u64,
-    sol_collected: u64,
-    tokens_sold: u64,
-) -> Result<u64> {
-    if sol_collected == 0 || tokens_sold == 0 {
-        return Ok(0);
-    }
+#[derive(Accounts)]
+pub struct ConsolidateFeeShards<'info> {
+    #[account(
+        mut,
+        seeds = [FEE_VAULT_SEED],
+        bump = fee_vault.bump
+    )]
+    pub fee_vault: Account<'info, FeeVault>,
+}
 
-    let virtual_sol_init = get_virtual_sol_init();
-    let virtual_token_init = get_virtual_token_init();
-    let constant_k = get_constant_k();
+#[derive(Accounts)]
+pub struct SweepFees<'info> {
+    #[account(
+        mut,
+        seeds = [FEE_VAULT_SEED],
+        bump = fee_vault.bump
+    )]
+    pub fee_vault: Account<'info, FeeVault>,
 
-    let current_virtual_sol = (virtual_sol_init as u128)
-        .checked_add(sol_collected as u128)
-        .ok_or(BonkError::MathOverflow)?;
+    #[account(seeds = [GLOBAL_CONFIG_SEED], bump = global_config.bump)]
+    pub global_config: Account<'info, GlobalConfig>,
 
-    let current_virtual_token = (virtual_token_init as u128)
-        .checked_sub(tokens_sold as u128)
-        .ok_or(BonkError::MathOverflow)?;
+    #[account(
+        mut,
+        address = global_config.treasury_wallet @ BonkError::InvalidTreasury
+    )]
+    /// CHECK: Treasury wallet address is verified against the global config
+    pub treasury_wallet: AccountInfo<'info>,
 
-    let new_virtual_token = current_virtual_token
-        .checked_add(token_amount as u128)
-        .ok_or(BonkError::MathOverflow)?;
+    #[account(address = global_config.admin @ BonkError::Unauthorized)]
+    pub admin: Signer<'info>,
+}
 
-    let new_virtual_sol = constant_k
-        .checked_div(new_virtual_token)
-        .ok_or(BonkError::MathOverflow)?;
+#[derive(Accounts)]
+pub struct InitializeRoleRegistry<'info> {
+    #[account(
+        init,
+        payer = admin,
+        space = 8 + 4 + MAX_ROLE_MEMBERS * (1 + 32) + 1,
+        seeds = [ROLE_REGISTRY_SEED],
+        bump
+    )]
+    pub role_registry: Account<'info, RoleRegistry>,
 
-    let sol_out = current_virtual_sol
-        .checked_sub(new_virtual_sol)
-        .ok_or(BonkError::MathOverflow)?;
+    #[account(seeds = [GLOBAL_CONFIG_SEED], bump = global_config.bump)]
+    pub global_config: Account<'info, GlobalConfig>,
 
-    let sol_out_u64 = sol_out as u64;
-    if sol_out_u64 > sol_collected {
-        return Ok(sol_collected.checked_mul(98).unwrap().checked_div(100).unwrap());
-    }
+    #[account(
+        mut,
+        address = global_config.admin @ BonkError::Unauthorized
+    )]
+    pub admin: Signer<'info>,
 
-    Ok(sol_out_u64)
+    pub system_program: Program<'info, System>,
 }
 
-// =================================================================
-// ACCOUNT STRUCTURES
-// =================================================================
+#[derive(Accounts)]
+pub struct ManageRole<'info> {
+    #[account(
+        mut,
+        seeds = [ROLE_REGISTRY_SEED],
+        bump = role_registry.bump
+    )]
+    pub role_registry: Account<'info, RoleRegistry>,
 
-#[account]
-pub struct PriceOracle {
-    pub sol_price_usd: u64,
-    pub last_update_timestamp: i64,
-    pub next_update_timestamp: i64,
-    pub keeper_authority: Pubkey,
-    pub update_count: u64,
-}
+    #[account(seeds = [GLOBAL_CONFIG_SEED], bump = global_config.bump)]
+    pub global_config: Account<'info, GlobalConfig>,
 
-#[account]
-pub struct TokenBattleState {
-    pub mint: Pubkey,
-    pub sol_collected: u64,
-    pub tokens_sold: u64,
-    pub total_trade_volume: u64,
-    pub is_active: bool,
-    pub battle_status: BattleStatus,
-    pub opponent_mint: Pubkey,
-    pub creation_timestamp: i64,
-    pub last_trade_timestamp: i64,
-    pub battle_start_timestamp: i64,
-    pub victory_timestamp: i64,
-    pub listing_timestamp: i64,
-    pub bump: u8,
-    pub name: String,
-    pub symbol: String,
-    pub uri: String,
+    #[account(
+        address = global_config.admin @ BonkError::Unauthorized
+    )]
+    pub admin: Signer<'info>,
 }
 
-#[derive(AnchorSerialize, AnchorDeserialize, Clone, PartialEq, Eq, Debug)]
-pub enum BattleStatus {
-    Created,
-    Qualified,
-    InBattle,
-    VictoryPending,
-    Listed,
-    Defeated,
+#[derive(Accounts)]
+pub struct EmergencyPauseAll<'info> {
+    #[account(
+        mut,
+        seeds = [GLOBAL_CONFIG_SEED],
+        bump = global_config.bump
+    )]
+    pub global_config: Account<'info, GlobalConfig>,
+
+    #[account(
+        address = global_config.admin @ BonkError::Unauthorized
+    )]
+    pub admin: Signer<'info>,
 }
 
-// =================================================================
-// ACCOUNT CONTEXTS
-// =================================================================
+#[derive(Accounts)]
+pub struct EmergencyPause<'info> {
+    #[account(
+        mut,
+        seeds = [b"battle_state", token_battle_state.mint.as_ref()],
+        bump = token_battle_state.bump,
+        constraint = token_battle_state.version == BATTLE_STATE_VERSION @ BonkError::OutdatedStateVersion
+    )]
+    pub token_battle_state: Account<'info, TokenBattleState>,
+
+    #[account(seeds = [GLOBAL_CONFIG_SEED], bump = global_config.bump)]
+    pub global_config: Account<'info, GlobalConfig>,
+
+    #[account(
+        address = global_config.admin @ BonkError::Unauthorized
+    )]
+    pub admin: Signer<'info>,
+}
 
 #[derive(Accounts)]
 pub struct InitializePriceOracle<'info> {
@@ -1164,10 +3799,12 @@ pub struct InitializePriceOracle<'info> {
     )]
     pub price_oracle: Account<'info, PriceOracle>,
 
+    #[account(seeds = [GLOBAL_CONFIG_SEED], bump = global_config.bump)]
+    pub global_config: Account<'info, GlobalConfig>,
+
     #[account(
         mut,
-        signer,
-        address = KEEPER_AUTHORITY.parse::<Pubkey>().unwrap() @ BonkError::Unauthorized
+        address = global_config.keeper_authority @ BonkError::Unauthorized
     )]
     pub keeper_authority: Signer<'info>,
 
@@ -1183,52 +3820,151 @@ pub struct UpdateSolPrice<'info> {
     )]
     pub price_oracle: Account<'info, PriceOracle>,
 
+    #[account(seeds = [ROLE_REGISTRY_SEED], bump = role_registry.bump)]
+    pub role_registry: Account<'info, RoleRegistry>,
+
     #[account(
-        signer,
-        address = KEEPER_AUTHORITY.parse::<Pubkey>().unwrap() @ BonkError::Unauthorized
+        constraint = role_registry.has_role(KeeperRole::OracleUpdater, &oracle_updater.key()) @ BonkError::MissingRole
     )]
-    pub keeper_authority: Signer<'info>,
+    pub oracle_updater: Signer<'info>,
 }
 
 #[derive(Accounts)]
-#[instruction(name: String, symbol: String, uri: String)]
+#[instruction(name: String, symbol: String, uri: String, tier: u8)]
 pub struct CreateBattleToken<'info> {
+    // Created in the handler: the extensions it gets depend on token_program
+    #[account(mut)]
+    pub mint: Signer<'info>,
+
     #[account(
         init,
         payer = user,
-        mint::decimals = 9,
-        mint::authority = token_battle_state,
-        mint::token_program = token_program,
+        space = if token_program.key() == anchor_spl::token_2022::ID {
+            TokenBattleState::space_for("", "", "")
+        } else {
+            TokenBattleState::space_for(&name, &symbol, &uri)
+        },
+        seeds = [b"battle_state", mint.key().as_ref()],
+        bump
     )]
-    pub mint: InterfaceAccount<'info, Mint>,
+    pub token_battle_state: Account<'info, TokenBattleState>,
+
+    #[account(mut)]
+    /// CHECK: Created in the handler by the associated token program, which checks the address
+    pub contract_token_account: UncheckedAccount<'info>,
 
     #[account(
         init,
         payer = user,
-        space = 8 + 32 + 8 + 8 + 8 + 1 + 1 + 32 + 8 + 8 + 8 + 8 + 8 + 1 + (4 + 50) + (4 + 10) + (4 + 200) + 64,
+        space = 8 + 32 + 8 + 8 + 1,
+        seeds = [CREATOR_VAULT_SEED, mint.key().as_ref()],
+        bump
+    )]
+    pub creator_vault: Account<'info, CreatorVault>,
+
+    // Only required when create_battle_token carries an initial_buy_sol
+    #[account(mut)]
+    /// CHECK: Created in the handler by the associated token program, which checks the address
+    pub creator_token_account: Option<UncheckedAccount<'info>>,
+
+    // Only required when create_battle_token carries an initial_buy_sol
+    #[account(
+        init_if_needed,
+        payer = user,
+        space = 8 + 32 + 32 + 8 + 8 + 1,
+        seeds = [TRADER_ACTIVITY_SEED, mint.key().as_ref(), user.key().as_ref()],
+        bump
+    )]
+    pub trader_activity: Option<Account<'info, TraderActivity>>,
+
+    // Only required when create_battle_token carries an initial_buy_sol
+    #[account(
+        init_if_needed,
+        payer = user,
+        space = 8 + 32 + 32 + 8 + 8 + 8 + 8 + 8 + 8 + 8 + 1,
+        seeds = [USER_POSITION_SEED, mint.key().as_ref(), user.key().as_ref()],
+        bump
+    )]
+    pub user_position: Option<Account<'info, UserPosition>>,
+
+    // Only required for an initial_buy_sol while the launch window is open
+    #[account(
+        init_if_needed,
+        payer = user,
+        space = 8 + 32 + 32 + 8 + 1,
+        seeds = [LAUNCH_ALLOCATION_SEED, mint.key().as_ref(), user.key().as_ref()],
+        bump
+    )]
+    pub launch_allocation: Option<Account<'info, LaunchAllocation>>,
+
+    #[account(seeds = [b"price_oracle"], bump)]
+    pub price_oracle: Account<'info, PriceOracle>,
+
+    #[account(seeds = [GLOBAL_CONFIG_SEED], bump = global_config.bump)]
+    pub global_config: Account<'info, GlobalConfig>,
+
+    // Only required when create_battle_token carries an initial_buy_sol
+    #[account(
+        mut,
+        seeds = [FEE_SHARD_SEED, &[fee_shard_index(&mint.key())]],
+        bump = fee_shard.bump
+    )]
+    pub fee_shard: Option<Account<'info, FeeShard>>,
+
+    #[account(mut)]
+    pub user: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+    pub token_program: Interface<'info, TokenInterface>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+}
+
+#[derive(Accounts)]
+pub struct MigrateConfig<'info> {
+    #[account(
+        mut,
+        owner = crate::ID,
+        seeds = [GLOBAL_CONFIG_SEED],
+        bump
+    )]
+    /// CHECK: Deserialized by hand after realloc - old layouts do not fit Account<>
+    pub global_config: UncheckedAccount<'info>,
+
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct MigrateBattleState<'info> {
+    #[account(
+        mut,
+        owner = crate::ID,
         seeds = [b"battle_state", mint.key().as_ref()],
         bump
     )]
-    pub token_battle_state: Account<'info, TokenBattleState>,
+    /// CHECK: Deserialized by hand after realloc - old layouts are too short for Account<>
+    pub token_battle_state: UncheckedAccount<'info>,
+
+    pub mint: InterfaceAccount<'info, Mint>,
 
     #[account(
-        init,
-        payer = user,
-        associated_token::mint = mint,
-        associated_token::authority = token_battle_state,
-        associated_token::token_program = token_program,
+        init_if_needed,
+        payer = payer,
+        space = 8 + 32 + 8 + 8 + 1,
+        seeds = [CREATOR_VAULT_SEED, mint.key().as_ref()],
+        bump
     )]
-    pub contract_token_account: InterfaceAccount<'info, TokenAccount>,
+    pub creator_vault: Account<'info, CreatorVault>,
 
-    #[account(seeds = [b"price_oracle"], bump)]
-    pub price_oracle: Account<'info, PriceOracle>,
+    #[account(seeds = [GLOBAL_CONFIG_SEED], bump = global_config.bump)]
+    pub global_config: Account<'info, GlobalConfig>,
 
     #[account(mut)]
-    pub user: Signer<'info>,
+    pub payer: Signer<'info>,
 
     pub system_program: Program<'info, System>,
-    pub token_program: Interface<'info, TokenInterface>,
-    pub associated_token_program: Program<'info, AssociatedToken>,
 }
 
 #[derive(Accounts)]
@@ -1236,7 +3972,8 @@ pub struct BuyToken<'info> {
     #[account(
         mut,
         seeds = [b"battle_state", mint.key().as_ref()],
-        bump = token_battle_state.bump
+        bump = token_battle_state.bump,
+        constraint = token_battle_state.version == BATTLE_STATE_VERSION @ BonkError::OutdatedStateVersion
     )]
     pub token_battle_state: Account<'info, TokenBattleState>,
 
@@ -1263,12 +4000,58 @@ pub struct BuyToken<'info> {
     #[account(seeds = [b"price_oracle"], bump)]
     pub price_oracle: Account<'info, PriceOracle>,
 
+    #[account(seeds = [GLOBAL_CONFIG_SEED], bump = global_config.bump)]
+    pub global_config: Account<'info, GlobalConfig>,
+
     #[account(
         mut,
-        address = TREASURY_WALLET.parse::<Pubkey>().unwrap() @ BonkError::InvalidTreasury
+        seeds = [FEE_SHARD_SEED, &[fee_shard_index(&mint.key())]],
+        bump = fee_shard.bump
     )]
-    /// CHECK: Treasury wallet address is hardcoded and verified
-    pub treasury_wallet: AccountInfo<'info>,
+    pub fee_shard: Account<'info, FeeShard>,
+
+    // Only required while the launch window is open
+    #[account(
+        init_if_needed,
+        payer = user,
+        space = 8 + 32 + 32 + 8 + 1,
+        seeds = [LAUNCH_ALLOCATION_SEED, mint.key().as_ref(), user.key().as_ref()],
+        bump
+    )]
+    pub launch_allocation: Option<Account<'info, LaunchAllocation>>,
+
+    #[account(
+        init_if_needed,
+        payer = user,
+        space = 8 + 32 + 32 + 8 + 8 + 1,
+        seeds = [TRADER_ACTIVITY_SEED, mint.key().as_ref(), user.key().as_ref()],
+        bump
+    )]
+    pub trader_activity: Account<'info, TraderActivity>,
+
+    #[account(
+        init_if_needed,
+        payer = user,
+        space = 8 + 32 + 32 + 8 + 8 + 8 + 8 + 8 + 8 + 8 + 1,
+        seeds = [USER_POSITION_SEED, mint.key().as_ref(), user.key().as_ref()],
+        bump
+    )]
+    pub user_position: Account<'info, UserPosition>,
+
+    #[account(
+        mut,
+        seeds = [CREATOR_VAULT_SEED, mint.key().as_ref()],
+        bump = creator_vault.bump
+    )]
+    pub creator_vault: Account<'info, CreatorVault>,
+
+    #[account(
+        mut,
+        seeds = [REFERRAL_VAULT_SEED, referral_vault.referrer.as_ref()],
+        bump = referral_vault.bump,
+        constraint = referral_vault.referrer != user.key() @ BonkError::SelfReferral
+    )]
+    pub referral_vault: Option<Account<'info, ReferralVault>>,
 
     #[account(mut)]
     pub user: Signer<'info>,
@@ -1278,12 +4061,40 @@ pub struct BuyToken<'info> {
     pub associated_token_program: Program<'info, AssociatedToken>,
 }
 
+#[derive(Accounts)]
+pub struct CloseTraderAccounts<'info> {
+    #[account(
+        mut,
+        close = user,
+        seeds = [TRADER_ACTIVITY_SEED, mint.key().as_ref(), user.key().as_ref()],
+        bump = trader_activity.bump
+    )]
+    pub trader_activity: Account<'info, TraderActivity>,
+
+    #[account(
+        mut,
+        close = user,
+        seeds = [USER_POSITION_SEED, mint.key().as_ref(), user.key().as_ref()],
+        bump = user_position.bump
+    )]
+    pub user_position: Account<'info, UserPosition>,
+
+    pub mint: InterfaceAccount<'info, Mint>,
+
+    #[account(seeds = [GLOBAL_CONFIG_SEED], bump = global_config.bump)]
+    pub global_config: Account<'info, GlobalConfig>,
+
+    #[account(mut)]
+    pub user: Signer<'info>,
+}
+
 #[derive(Accounts)]
 pub struct SellToken<'info> {
     #[account(
         mut,
         seeds = [b"battle_state", mint.key().as_ref()],
-        bump = token_battle_state.bump
+        bump = token_battle_state.bump,
+        constraint = token_battle_state.version == BATTLE_STATE_VERSION @ BonkError::OutdatedStateVersion
     )]
     pub token_battle_state: Account<'info, TokenBattleState>,
 
@@ -1309,12 +4120,48 @@ pub struct SellToken<'info> {
     #[account(seeds = [b"price_oracle"], bump)]
     pub price_oracle: Account<'info, PriceOracle>,
 
+    #[account(seeds = [GLOBAL_CONFIG_SEED], bump = global_config.bump)]
+    pub global_config: Account<'info, GlobalConfig>,
+
     #[account(
         mut,
-        address = TREASURY_WALLET.parse::<Pubkey>().unwrap() @ BonkError::InvalidTreasury
+        seeds = [FEE_SHARD_SEED, &[fee_shard_index(&mint.key())]],
+        bump = fee_shard.bump
     )]
-    /// CHECK: Treasury wallet address is hardcoded and verified
-    pub treasury_wallet: AccountInfo<'info>,
+    pub fee_shard: Account<'info, FeeShard>,
+
+    #[account(
+        init_if_needed,
+        payer = user,
+        space = 8 + 32 + 32 + 8 + 8 + 1,
+        seeds = [TRADER_ACTIVITY_SEED, mint.key().as_ref(), user.key().as_ref()],
+        bump
+    )]
+    pub trader_activity: Account<'info, TraderActivity>,
+
+    #[account(
+        init_if_needed,
+        payer = user,
+        space = 8 + 32 + 32 + 8 + 8 + 8 + 8 + 8 + 8 + 8 + 1,
+        seeds = [USER_POSITION_SEED, mint.key().as_ref(), user.key().as_ref()],
+        bump
+    )]
+    pub user_position: Account<'info, UserPosition>,
+
+    #[account(
+        mut,
+        seeds = [CREATOR_VAULT_SEED, mint.key().as_ref()],
+        bump = creator_vault.bump
+    )]
+    pub creator_vault: Account<'info, CreatorVault>,
+
+    #[account(
+        mut,
+        seeds = [REFERRAL_VAULT_SEED, referral_vault.referrer.as_ref()],
+        bump = referral_vault.bump,
+        constraint = referral_vault.referrer != user.key() @ BonkError::SelfReferral
+    )]
+    pub referral_vault: Option<Account<'info, ReferralVault>>,
 
     #[account(mut)]
     pub user: Signer<'info>,
@@ -1323,27 +4170,119 @@ pub struct SellToken<'info> {
     pub token_program: Interface<'info, TokenInterface>,
 }
 
+#[derive(Accounts)]
+pub struct ClaimCreatorFees<'info> {
+    #[account(
+        seeds = [b"battle_state", mint.key().as_ref()],
+        bump = token_battle_state.bump,
+        constraint = token_battle_state.version == BATTLE_STATE_VERSION @ BonkError::OutdatedStateVersion,
+        constraint = token_battle_state.creator == creator.key() @ BonkError::NotCreator
+    )]
+    pub token_battle_state: Account<'info, TokenBattleState>,
+
+    pub mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        mut,
+        seeds = [CREATOR_VAULT_SEED, mint.key().as_ref()],
+        bump = creator_vault.bump
+    )]
+    pub creator_vault: Account<'info, CreatorVault>,
+
+    #[account(mut)]
+    pub creator: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct RegisterReferrer<'info> {
+    #[account(
+        init,
+        payer = referrer,
+        space = 8 + 32 + 8 + 8 + 1,
+        seeds = [REFERRAL_VAULT_SEED, referrer.key().as_ref()],
+        bump
+    )]
+    pub referral_vault: Account<'info, ReferralVault>,
+
+    #[account(mut)]
+    pub referrer: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ClaimReferralRewards<'info> {
+    #[account(
+        mut,
+        seeds = [REFERRAL_VAULT_SEED, referrer.key().as_ref()],
+        bump = referral_vault.bump
+    )]
+    pub referral_vault: Account<'info, ReferralVault>,
+
+    #[account(mut)]
+    pub referrer: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct QuoteTrade<'info> {
+    #[account(
+        seeds = [b"battle_state", mint.key().as_ref()],
+        bump = token_battle_state.bump,
+        constraint = token_battle_state.version == BATTLE_STATE_VERSION @ BonkError::OutdatedStateVersion
+    )]
+    pub token_battle_state: Account<'info, TokenBattleState>,
+
+    pub mint: InterfaceAccount<'info, Mint>,
+
+    #[account(seeds = [GLOBAL_CONFIG_SEED], bump = global_config.bump)]
+    pub global_config: Account<'info, GlobalConfig>,
+
+    /// CHECK: Only a seed - the wallet the quote is for
+    pub wallet: UncheckedAccount<'info>,
+
+    // The wallet's wash-trade history, if any; omitted for a fresh wallet
+    #[account(
+        seeds = [TRADER_ACTIVITY_SEED, mint.key().as_ref(), wallet.key().as_ref()],
+        bump = trader_activity.bump
+    )]
+    pub trader_activity: Option<Account<'info, TraderActivity>>,
+
+    // The wallet's launch-window spend, if any; omitted for a fresh wallet
+    #[account(
+        seeds = [LAUNCH_ALLOCATION_SEED, mint.key().as_ref(), wallet.key().as_ref()],
+        bump = launch_allocation.bump
+    )]
+    pub launch_allocation: Option<Account<'info, LaunchAllocation>>,
+}
+
 #[derive(Accounts)]
 pub struct StartBattle<'info> {
     #[account(
         mut,
         seeds = [b"battle_state", token_a_state.mint.as_ref()],
-        bump = token_a_state.bump
+        bump = token_a_state.bump,
+        constraint = token_a_state.version == BATTLE_STATE_VERSION @ BonkError::OutdatedStateVersion
     )]
     pub token_a_state: Account<'info, TokenBattleState>,
 
     #[account(
         mut,
         seeds = [b"battle_state", token_b_state.mint.as_ref()],
-        bump = token_b_state.bump
+        bump = token_b_state.bump,
+        constraint = token_b_state.version == BATTLE_STATE_VERSION @ BonkError::OutdatedStateVersion
     )]
     pub token_b_state: Account<'info, TokenBattleState>,
 
+    #[account(seeds = [GLOBAL_CONFIG_SEED], bump = global_config.bump)]
+    pub global_config: Account<'info, GlobalConfig>,
+
+    #[account(seeds = [ROLE_REGISTRY_SEED], bump = role_registry.bump)]
+    pub role_registry: Account<'info, RoleRegistry>,
+
     #[account(
-        signer,
-        address = KEEPER_AUTHORITY.parse::<Pubkey>().unwrap() @ BonkError::Unauthorized
+        constraint = role_registry.has_role(KeeperRole::Matchmaker, &matchmaker.key()) @ BonkError::MissingRole
     )]
-    pub keeper_authority: Signer<'info>,
+    pub matchmaker: Signer<'info>,
 
     pub system_program: Program<'info, System>,
 }
@@ -1354,7 +4293,8 @@ pub struct CheckVictory<'info> {
         mut,
         seeds = [b"battle_state", token_battle_state.mint.as_ref()],
         bump = token_battle_state.bump,
-        constraint = token_battle_state.battle_status == BattleStatus::InBattle @ BonkError::NotInBattle
+        constraint = token_battle_state.battle_status == BattleStatus::InBattle @ BonkError::NotInBattle,
+        constraint = token_battle_state.version == BATTLE_STATE_VERSION @ BonkError::OutdatedStateVersion
     )]
     pub token_battle_state: Account<'info, TokenBattleState>,
 
@@ -1368,7 +4308,8 @@ pub struct FinalizeDuel<'info> {
         mut,
         seeds = [b"battle_state", winner_state.mint.as_ref()],
         bump = winner_state.bump,
-        constraint = winner_state.battle_status == BattleStatus::VictoryPending @ BonkError::NoVictoryAchieved
+        constraint = winner_state.battle_status == BattleStatus::VictoryPending @ BonkError::NoVictoryAchieved,
+        constraint = winner_state.version == BATTLE_STATE_VERSION @ BonkError::OutdatedStateVersion
     )]
     pub winner_state: Account<'info, TokenBattleState>,
 
@@ -1378,22 +4319,35 @@ pub struct FinalizeDuel<'info> {
         bump = loser_state.bump,
         constraint = loser_state.battle_status == BattleStatus::InBattle @ BonkError::InvalidBattleState,
         constraint = loser_state.mint == winner_state.opponent_mint @ BonkError::NotOpponents,
-        constraint = winner_state.mint == loser_state.opponent_mint @ BonkError::NotOpponents
+        constraint = winner_state.mint == loser_state.opponent_mint @ BonkError::NotOpponents,
+        constraint = loser_state.version == BATTLE_STATE_VERSION @ BonkError::OutdatedStateVersion
     )]
     pub loser_state: Account<'info, TokenBattleState>,
 
+    #[account(seeds = [GLOBAL_CONFIG_SEED], bump = global_config.bump)]
+    pub global_config: Account<'info, GlobalConfig>,
+
     #[account(
         mut,
-        address = TREASURY_WALLET.parse::<Pubkey>().unwrap() @ BonkError::InvalidTreasury
+        address = global_config.treasury_wallet @ BonkError::InvalidTreasury
     )]
-    /// CHECK: Treasury wallet address is hardcoded and verified
+    /// CHECK: Treasury wallet address is verified against the global config
     pub treasury_wallet: AccountInfo<'info>,
 
     #[account(
         mut,
-        address = KEEPER_AUTHORITY.parse::<Pubkey>().unwrap() @ BonkError::Unauthorized
+        address = global_config.keeper_authority @ BonkError::Unauthorized
     )]
-    pub keeper_authority: Signer<'info>,
+    /// CHECK: Keeper wallet address is verified against the global config
+    pub keeper_authority: AccountInfo<'info>,
+
+    #[account(seeds = [ROLE_REGISTRY_SEED], bump = role_registry.bump)]
+    pub role_registry: Account<'info, RoleRegistry>,
+
+    #[account(
+        constraint = role_registry.has_role(KeeperRole::Finalizer, &finalizer.key()) @ BonkError::MissingRole
+    )]
+    pub finalizer: Signer<'info>,
 
     pub system_program: Program<'info, System>,
 }
@@ -1404,7 +4358,8 @@ pub struct WithdrawForListing<'info> {
         mut,
         seeds = [b"battle_state", mint.key().as_ref()],
         bump = token_battle_state.bump,
-        constraint = token_battle_state.battle_status == BattleStatus::Listed @ BonkError::NotReadyForListing
+        constraint = token_battle_state.battle_status == BattleStatus::Listed @ BonkError::NotReadyForListing,
+        constraint = token_battle_state.version == BATTLE_STATE_VERSION @ BonkError::OutdatedStateVersion
     )]
     pub token_battle_state: Account<'info, TokenBattleState>,
 
@@ -1427,11 +4382,24 @@ pub struct WithdrawForListing<'info> {
     )]
     pub keeper_token_account: InterfaceAccount<'info, TokenAccount>,
 
+    #[account(seeds = [GLOBAL_CONFIG_SEED], bump = global_config.bump)]
+    pub global_config: Account<'info, GlobalConfig>,
+
+    // Listing liquidity always lands in the configured keeper wallet
     #[account(
         mut,
-        address = KEEPER_AUTHORITY.parse::<Pubkey>().unwrap() @ BonkError::Unauthorized
+        address = global_config.keeper_authority @ BonkError::Unauthorized
     )]
-    pub keeper_authority: Signer<'info>,
+    /// CHECK: Keeper wallet address is verified against the global config
+    pub keeper_authority: AccountInfo<'info>,
+
+    #[account(seeds = [ROLE_REGISTRY_SEED], bump = role_registry.bump)]
+    pub role_registry: Account<'info, RoleRegistry>,
+
+    #[account(
+        constraint = role_registry.has_role(KeeperRole::Lister, &lister.key()) @ BonkError::MissingRole
+    )]
+    pub lister: Signer<'info>,
 
     pub system_program: Program<'info, System>,
     pub token_program: Interface<'info, TokenInterface>,
@@ -1442,6 +4410,109 @@ pub struct WithdrawForListing<'info> {
 // EVENTS
 // =================================================================
 
+#[event]
+pub struct ConfigUpdated {
+    pub admin: Pubkey,
+    pub keeper_authority: Pubkey,
+    pub treasury_wallet: Pubkey,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct AuthorityTransferProposed {
+    pub role: AuthorityRole,
+    pub current_authority: Pubkey,
+    pub pending_authority: Pubkey,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct AuthorityTransferAccepted {
+    pub role: AuthorityRole,
+    pub previous_authority: Pubkey,
+    pub new_authority: Pubkey,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct AuthorityTransferCancelled {
+    pub role: AuthorityRole,
+    pub cancelled_authority: Pubkey,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct ParamChangeQueued {
+    pub kind: ParamKind,
+    pub current_value: u64,
+    pub new_value: u64,
+    pub activation_timestamp: i64,
+}
+
+#[event]
+pub struct ParamChangeExecuted {
+    pub kind: ParamKind,
+    pub old_value: u64,
+    pub new_value: u64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct ParamChangeCancelled {
+    pub kind: ParamKind,
+    pub cancelled_value: u64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct RoleGranted {
+    pub role: KeeperRole,
+    pub member: Pubkey,
+    pub admin: Pubkey,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct RoleRevoked {
+    pub role: KeeperRole,
+    pub member: Pubkey,
+    pub admin: Pubkey,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct EmergencyPaused {
+    pub mint: Pubkey, // Pubkey::default() for a program-wide pause
+    pub admin: Pubkey,
+    pub paused_until: i64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct EmergencyResumed {
+    pub mint: Pubkey, // Pubkey::default() for a program-wide resume
+    pub admin: Pubkey,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct ConfigMigrated {
+    pub from_version: u8,
+    pub to_version: u8,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct BattleStateMigrated {
+    pub mint: Pubkey,
+    pub name: String,   // empty for Token-2022 mints
+    pub symbol: String,
+    pub uri: String,
+    pub from_version: u8,
+    pub to_version: u8,
+    pub timestamp: i64,
+}
+
 #[event]
 pub struct PriceUpdated {
     pub previous_price: u64,
@@ -1454,11 +4525,74 @@ pub struct PriceUpdated {
 pub struct GladiatorForged {
     pub mint: Pubkey,
     pub creator: Pubkey,
+    pub tier: u8,
     pub target_sol: u64,
     pub victory_volume_sol: u64,
     pub initial_market_cap_usd: u64,
     pub sol_price_at_creation: u64,
     pub is_test_tier: bool,
+    pub launch_window_secs: u64,
+    pub launch_window_end: i64,
+    pub launch_wallet_cap_sol: u64,
+    pub name: String,
+    pub symbol: String,
+    pub uri: String,
+    pub metadata_on_mint: bool, // name/symbol/uri also live in the mint's TokenMetadata
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct TraderAccountsClosed {
+    pub mint: Pubkey,
+    pub trader: Pubkey,
+    pub trade_count: u64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct CreatorDevBuy {
+    pub mint: Pubkey,
+    pub creator: Pubkey,
+    pub sol_amount: u64,
+    pub fee: u64,
+    pub tokens_received: u64,
+    pub supply_share_bps: u64,
+    pub sol_collected: u64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct CreatorFeesClaimed {
+    pub mint: Pubkey,
+    pub creator: Pubkey,
+    pub amount: u64,
+    pub total_claimed: u64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct ReferralRewardsClaimed {
+    pub referrer: Pubkey,
+    pub amount: u64,
+    pub total_claimed: u64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct FeeShardsConsolidated {
+    pub shards: u8,
+    pub amount: u64,
+    pub total_accrued: u64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct FeesSwept {
+    pub treasury: Pubkey,
+    pub amount: u64,
+    pub epoch: u64,
+    pub epoch_swept: u64,
+    pub total_swept: u64,
     pub timestamp: i64,
 }
 
@@ -1476,10 +4610,14 @@ pub struct TokenPurchased {
     pub buyer: Pubkey,
     pub sol_amount: u64,
     pub tokens_received: u64,
+    pub fee: u64,
+    pub fee_bps: u64,
     pub sol_collected: u64,
     pub total_volume_sol: u64,
+    pub battle_volume_sol: u64,
     pub market_cap_usd: u64,
     pub sol_price: u64,
+    pub referrer: Option<Pubkey>,
 }
 
 #[event]
@@ -1488,10 +4626,14 @@ pub struct TokenSold {
     pub seller: Pubkey,
     pub token_amount: u64,
     pub sol_received: u64,
+    pub fee: u64,
+    pub fee_bps: u64,
     pub sol_collected: u64,
     pub total_volume_sol: u64,
+    pub battle_volume_sol: u64,
     pub market_cap_usd: u64,
     pub sol_price: u64,
+    pub referrer: Option<Pubkey>,
 }
 
 #[event]
@@ -1502,6 +4644,10 @@ pub struct BattleStarted {
     pub sol_b: u64,
     pub target_sol: u64,
     pub victory_volume_sol: u64,
+    pub target_sol_gain_a: u64,
+    pub target_sol_gain_b: u64,
+    pub target_volume_a: u64,
+    pub target_volume_b: u64,
     pub timestamp: i64,
 }
 
@@ -1510,8 +4656,11 @@ pub struct VictoryAchieved {
     pub winner_mint: Pubkey,
     pub sol_collected: u64,
     pub volume_sol: u64,
-    pub target_sol: u64,
-    pub victory_volume_sol: u64,
+    pub battle_volume_sol: u64,
+    pub sol_gained: u64,
+    pub volume_gained: u64,
+    pub battle_target_sol: u64,
+    pub battle_target_volume: u64,
     pub final_mc_usd: u64,
     pub final_volume_usd: u64,
     pub victory_timestamp: i64,
@@ -1529,12 +4678,20 @@ pub struct DuelFinalized {
     pub timestamp: i64,
 }
 
+#[event]
+pub struct SupplyFinalized {
+    pub mint: Pubkey,
+    pub total_supply: u64,
+    pub timestamp: i64,
+}
+
 #[event]
 pub struct ListingWithdrawal {
     pub mint: Pubkey,
     pub sol_withdrawn: u64,
     pub tokens_withdrawn: u64,
     pub keeper: Pubkey,
+    pub lister: Pubkey,
     pub timestamp: i64,
 }
 
@@ -1602,4 +4759,78 @@ pub enum BonkError {
     VictoryAlreadyAchieved,
     #[msg("Token already listed - trading closed")]
     TokenAlreadyListed,
-}
\ No newline at end of file
+    #[msg("Invalid tier")]
+    InvalidTier,
+    #[msg("Tokens belong to different tiers")]
+    TierMismatch,
+    #[msg("No authority transfer pending for this role")]
+    NoPendingAuthority,
+    #[msg("Invalid pending authority")]
+    InvalidPendingAuthority,
+    #[msg("Parameter value outside allowed bounds")]
+    ParamOutOfBounds,
+    #[msg("Parameter change timelock has not elapsed")]
+    ParamChangeNotReady,
+    #[msg("Trading is paused by an emergency stop")]
+    TradingPaused,
+    #[msg("Pause duration must be between 1 second and 72 hours")]
+    InvalidPauseDuration,
+    #[msg("Already paused")]
+    AlreadyPaused,
+    #[msg("Not paused")]
+    NotPaused,
+    #[msg("Pause cooldown still active")]
+    PauseCooldownActive,
+    #[msg("Signer does not hold the required role")]
+    MissingRole,
+    #[msg("Role already granted to this member")]
+    RoleAlreadyGranted,
+    #[msg("Role not granted to this member")]
+    RoleNotGranted,
+    #[msg("Role registry is full")]
+    RoleRegistryFull,
+    #[msg("Battle state is outdated - call migrate_battle_state first")]
+    OutdatedStateVersion,
+    #[msg("Battle state is already at the current version")]
+    AlreadyMigrated,
+    #[msg("Slippage exceeded: output below the requested minimum")]
+    SlippageExceeded,
+    #[msg("Trade expired: deadline has passed")]
+    TradeExpired,
+    #[msg("Launch window is open: the buyer's launch allocation account is required")]
+    LaunchAllocationRequired,
+    #[msg("Launch window wallet cap exceeded")]
+    LaunchWalletCapExceeded,
+    #[msg("Creator token account is required for an initial dev-buy")]
+    CreatorTokenAccountRequired,
+    #[msg("Only the token creator can do this")]
+    NotCreator,
+    #[msg("Nothing to claim")]
+    NothingToClaim,
+    #[msg("Cannot refer your own trades")]
+    SelfReferral,
+    #[msg("Fee sweep exceeds the per-epoch limit")]
+    SweepLimitExceeded,
+    #[msg("Invalid fee shard account")]
+    InvalidFeeShard,
+    #[msg("Trader activity account is required for a creator dev-buy")]
+    TraderActivityRequired,
+    #[msg("Virtual SOL reserve is empty - buys are disabled for this token")]
+    EmptyCurveReserve,
+    #[msg("Fee shard account is required for an initial dev-buy")]
+    FeeShardRequired,
+    #[msg("User position account is required for a creator dev-buy")]
+    UserPositionRequired,
+    #[msg("Trader accounts can only be closed once the wash-trade window has passed")]
+    WashWindowOpen,
+}
+
+impl From<CurveError> for BonkError {
+    fn from(err: CurveError) -> Self {
+        match err {
+            CurveError::MathOverflow | CurveError::DivisionByZero => BonkError::MathOverflow,
+            CurveError::ExceedsSupply => BonkError::ExceedsSupply,
+            CurveError::EmptyReserve => BonkError::EmptyCurveReserve,
+        }
+    }
+}